@@ -0,0 +1,384 @@
+//! Hats, collars and costumes drawn over the base sprites.
+//!
+//! An accessory is a small sprite sheet of its own plus a list saying which of its sprites goes
+//! on which frame of the cat, and where:
+//!
+//! ```toml
+//! image = "santa_hat.png"
+//! sprite_size = [16, 12]
+//!
+//! # Used for every frame not listed below. Leave it out to only draw on listed frames.
+//! default = { sprite = [0, 0], offset = [9, 2] }
+//!
+//! [[frames]]
+//! frame = [2, 0]   # sleeping, so the hat slides down
+//! sprite = [1, 0]
+//! offset = [4, 12]
+//! ```
+//!
+//! Offsets are in pixels of the unscaled base sheet, from the top left of the frame. Accessories
+//! are composited into the sheet as it loads, so they are scaled and tinted along with the cat.
+//! Frames generated for mirrored animations get the placement of the frame they were flipped
+//! from, flipped along with them.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use chrono::{Datelike, Local};
+use image::{imageops, GenericImageView, RgbaImage};
+use serde::Deserialize;
+
+use crate::config::{self, Config};
+use crate::sprite_sheet::{FrameRef, Problem, SheetError, SpriteSheet};
+
+/// How often to look at the clock for a new day.
+const DATE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccessoryManifest {
+    image: PathBuf,
+    sprite_size: (u32, u32),
+    default: Option<Placement>,
+    #[serde(default)]
+    frames: Vec<FramePlacement>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Placement {
+    sprite: FrameRef,
+    offset: (i32, i32),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FramePlacement {
+    frame: FrameRef,
+    sprite: FrameRef,
+    offset: (i32, i32),
+}
+
+pub struct Accessory {
+    image: RgbaImage,
+    sprite_size: (u32, u32),
+    default: Option<Placement>,
+    frames: HashMap<FrameRef, Placement>,
+}
+
+impl Accessory {
+    /// Reads an accessory manifest and its image, checking that every sprite it uses exists.
+    pub fn load(path: &Path) -> Result<Self, SheetError> {
+        let text = fs::read_to_string(path).map_err(|err| SheetError::Io(path.into(), err))?;
+        let manifest: AccessoryManifest =
+            toml::from_str(&text).map_err(|err| SheetError::Manifest(path.into(), err))?;
+        let image_path = path
+            .parent()
+            .map_or_else(|| manifest.image.clone(), |dir| dir.join(&manifest.image));
+        let image = image::open(&image_path)
+            .map_err(|err| SheetError::Image(image_path, err))?
+            .into_rgba8();
+
+        let problems = Self::validate(&manifest, &image);
+        if !problems.is_empty() {
+            return Err(SheetError::Invalid(path.into(), problems));
+        }
+
+        Ok(Self {
+            image,
+            sprite_size: manifest.sprite_size,
+            default: manifest.default,
+            frames: manifest
+                .frames
+                .iter()
+                .map(|frame| {
+                    let placement = Placement {
+                        sprite: frame.sprite,
+                        offset: frame.offset,
+                    };
+                    (frame.frame, placement)
+                })
+                .collect(),
+        })
+    }
+
+    /// The image a manifest refers to, resolved against the manifest's directory.
+    pub fn image_path(path: &Path) -> Option<PathBuf> {
+        let manifest: AccessoryManifest = toml::from_str(&fs::read_to_string(path).ok()?).ok()?;
+        Some(path.parent()?.join(manifest.image))
+    }
+
+    fn validate(manifest: &AccessoryManifest, image: &RgbaImage) -> Vec<Problem> {
+        let (width, height) = manifest.sprite_size;
+        if width == 0 || height == 0 {
+            return vec![Problem::EmptySpriteSize];
+        }
+        let grid = (image.width() / width, image.height() / height);
+        let out_of_bounds = |sprite: FrameRef| {
+            let (x, y, width, height) = sprite.bounds(manifest.sprite_size);
+            width == 0
+                || height == 0
+                || x.checked_add(width)
+                    .is_none_or(|right| right > image.width())
+                || y.checked_add(height)
+                    .is_none_or(|bottom| bottom > image.height())
+        };
+
+        let default = manifest
+            .default
+            .filter(|placement| out_of_bounds(placement.sprite))
+            .map(|placement| Problem::FrameOutOfBounds {
+                animation: "default".to_string(),
+                index: 0,
+                frame: placement.sprite,
+                grid,
+            });
+        let frames = manifest
+            .frames
+            .iter()
+            .enumerate()
+            .filter(|(_, frame)| out_of_bounds(frame.sprite))
+            .map(|(index, frame)| Problem::FrameOutOfBounds {
+                animation: "frames".to_string(),
+                index,
+                frame: frame.sprite,
+                grid,
+            });
+        default.into_iter().chain(frames).collect()
+    }
+
+    /// Draws the accessory over every frame of `sheet` that has a placement, clipped to the frame.
+    pub fn composite(&self, sheet: &mut SpriteSheet) {
+        let sprite_size = sheet.get_size();
+        for frame in sheet.frames() {
+            let source = sheet.mirror_source(frame);
+            let source_placement = source.and_then(|source| self.frames.get(&source));
+            let (placement, flip) = match (self.frames.get(&frame), source_placement) {
+                (Some(placement), _) => (placement, false),
+                (None, Some(placement)) => (placement, true),
+                (None, None) => match &self.default {
+                    Some(placement) => (placement, source.is_some()),
+                    None => continue,
+                },
+            };
+
+            let (sprite_x, sprite_y, width, height) = placement.sprite.bounds(self.sprite_size);
+            let mut sprite = self
+                .image
+                .view(sprite_x, sprite_y, width, height)
+                .to_image();
+            let mut cell = sheet.get_frame_view(frame).to_image();
+            let (mut offset_x, offset_y) = placement.offset;
+            if flip {
+                sprite = imageops::flip_horizontal(&sprite);
+                offset_x = cell.width() as i32 - offset_x - width as i32;
+            }
+            imageops::overlay(&mut cell, &sprite, offset_x.into(), offset_y.into());
+            let (x, y, _, _) = frame.bounds(sprite_size);
+            imageops::replace(sheet.image_mut(), &cell, x.into(), y.into());
+        }
+    }
+}
+
+/// A yearly range of days, such as `12-01..12-26`. Ranges may wrap around new year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct DateRange {
+    start: (u32, u32),
+    end: (u32, u32),
+}
+
+impl TryFrom<String> for DateRange {
+    type Error = String;
+
+    fn try_from(range: String) -> Result<Self, Self::Error> {
+        let parse_day = |day: &str| -> Option<(u32, u32)> {
+            let (month, day) = day.trim().split_once('-')?;
+            let (month, day) = (month.parse().ok()?, day.parse().ok()?);
+            ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some((month, day))
+        };
+        range
+            .split_once("..")
+            .and_then(|(start, end)| {
+                Some(DateRange {
+                    start: parse_day(start)?,
+                    end: parse_day(end)?,
+                })
+            })
+            .ok_or_else(|| format!("invalid date range {range:?}, expected MM-DD..MM-DD"))
+    }
+}
+
+impl DateRange {
+    /// Whether `(month, day)` falls in the range, both ends included.
+    pub fn contains(&self, day: (u32, u32)) -> bool {
+        if self.start <= self.end {
+            self.start <= day && day <= self.end
+        } else {
+            day >= self.start || day <= self.end
+        }
+    }
+}
+
+/// Today's `(month, day)` in local time.
+pub fn today() -> (u32, u32) {
+    let now = Local::now();
+    (now.month(), now.day())
+}
+
+/// Rebuilds the theme on a background thread whenever the day changes and the config has dated
+/// accessories, so they come and go without a restart. The config is whichever the theme was last
+/// loaded from.
+pub fn watch_dates() {
+    thread::Builder::new()
+        .name("dates".into())
+        .spawn(move || {
+            let mut day = today();
+            loop {
+                thread::sleep(DATE_CHECK_INTERVAL);
+                if today() == day {
+                    continue;
+                }
+                day = today();
+                let source = config::source();
+                let dated = source
+                    .path
+                    .as_deref()
+                    .and_then(|path| Config::load(path).ok())
+                    .is_some_and(|config| {
+                        config
+                            .accessories
+                            .iter()
+                            .any(|accessory| accessory.dates.is_some())
+                    });
+                if dated {
+                    config::apply(source.path.as_deref(), &source.overrides);
+                }
+            }
+        })
+        .expect("Error spawning date thread");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(text: &str) -> Result<DateRange, String> {
+        DateRange::try_from(text.to_string())
+    }
+
+    #[test]
+    fn parses_date_ranges() {
+        assert_eq!(
+            range("12-01..12-26"),
+            Ok(DateRange {
+                start: (12, 1),
+                end: (12, 26),
+            })
+        );
+        assert_eq!(
+            range(" 10-31 .. 11-01 "),
+            Ok(DateRange {
+                start: (10, 31),
+                end: (11, 1),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_date_ranges() {
+        for text in [
+            "",
+            "12-01",
+            "12-01..",
+            "..12-26",
+            "13-01..12-26",
+            "12-00..12-26",
+            "12-32..12-26",
+            "12/01..12/26",
+            "-1-01..12-26",
+            "12-01..12-26..12-31",
+        ] {
+            assert!(range(text).is_err(), "{text:?} parsed");
+        }
+    }
+
+    #[test]
+    fn contains_both_ends() {
+        let christmas = range("12-24..12-26").unwrap();
+        assert!(!christmas.contains((12, 23)));
+        assert!(christmas.contains((12, 24)));
+        assert!(christmas.contains((12, 25)));
+        assert!(christmas.contains((12, 26)));
+        assert!(!christmas.contains((12, 27)));
+    }
+
+    #[test]
+    fn contains_wraps_around_new_year() {
+        let winter = range("12-20..01-06").unwrap();
+        assert!(winter.contains((12, 31)));
+        assert!(winter.contains((1, 1)));
+        assert!(winter.contains((1, 6)));
+        assert!(!winter.contains((1, 7)));
+        assert!(!winter.contains((12, 19)));
+        assert!(!winter.contains((6, 15)));
+    }
+
+    #[test]
+    fn today_is_a_real_day() {
+        let (month, day) = today();
+        assert!((1..=12).contains(&month));
+        assert!((1..=31).contains(&day));
+    }
+
+    fn manifest(sprite: FrameRef) -> AccessoryManifest {
+        AccessoryManifest {
+            image: PathBuf::from("hat.png"),
+            sprite_size: (16, 16),
+            default: Some(Placement {
+                sprite,
+                offset: (0, 0),
+            }),
+            frames: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn accepts_sprites_inside_the_image() {
+        let image = RgbaImage::new(32, 16);
+        assert!(Accessory::validate(&manifest(FrameRef::Cell(1, 0)), &image).is_empty());
+    }
+
+    #[test]
+    fn rejects_sprites_outside_the_image() {
+        let image = RgbaImage::new(32, 16);
+        for sprite in [
+            FrameRef::Cell(2, 0),
+            FrameRef::Cell(0, 1),
+            FrameRef::Cell(u32::MAX, 0),
+            FrameRef::Rect {
+                x: u32::MAX,
+                y: 0,
+                width: 2,
+                height: 2,
+            },
+            FrameRef::Rect {
+                x: 0,
+                y: 1,
+                width: 4,
+                height: u32::MAX,
+            },
+        ] {
+            assert_eq!(
+                Accessory::validate(&manifest(sprite), &image).len(),
+                1,
+                "{sprite} passed"
+            );
+        }
+    }
+}
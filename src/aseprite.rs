@@ -0,0 +1,459 @@
+//! Sprite sheets exported from Aseprite.
+//!
+//! Export with *File > Export Sprite Sheet*, any layout, with *JSON Data* and *Tags* ticked, and
+//! point the config's `sprite_sheet` at the `.json` file. Either frame format works, hash or array.
+//!
+//! Each tag becomes the animation of the same name, so tag the timeline with `idle`, `run_ne` and
+//! the rest of [`ANIMATION_NAMES`]. Case, dashes and spaces do not matter, and a few names the
+//! original oneko or common sense would use are understood too: `sleep`, `awake`, `yawn`,
+//! `scratch`, `walk_n`, `wall_e` and so on. Frame durations carry over as they are, and tags
+//! playing in reverse or ping-pong are unrolled into plain loops.
+//!
+//! Trimmed frames are put back into their untrimmed size when the sheet loads, so the cat does not
+//! jump around between frames.
+//!
+//! [`ANIMATION_NAMES`]: crate::sprite_sheet::ANIMATION_NAMES
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use image::{imageops, GenericImageView, RgbaImage};
+use serde::Deserialize;
+
+use crate::sprite_sheet::{Animation, FrameRef, Problem, SheetError, SpriteSheet};
+
+/// Trimmed frames are laid out this many to a row once untrimmed.
+const COLUMNS: u32 = 16;
+
+/// Tag names that mean one of the cat's animations.
+const ALIASES: &[(&str, &str)] = &[
+    ("stand", "idle"),
+    ("stop", "idle"),
+    ("awake", "alert"),
+    ("yawn", "tired"),
+    ("sleep", "sleeping"),
+    ("scratch", "scratch_self"),
+    ("wash", "scratch_self"),
+];
+
+/// Tag name prefixes that mean one of the cat's directional animations.
+const PREFIXES: &[(&str, &str)] = &[
+    ("walk_", "run_"),
+    ("wall_", "scratch_wall_"),
+    ("scratch_", "scratch_wall_"),
+];
+
+#[derive(Debug, Deserialize)]
+struct Export {
+    frames: Frames,
+    meta: Meta,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Frames {
+    Array(Vec<Frame>),
+    /// Keyed by file name, in timeline order.
+    Hash(serde_json::Map<String, serde_json::Value>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Frame {
+    frame: Rect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    sprite_source_size: Rect,
+    source_size: Size,
+    /// Milliseconds.
+    duration: u64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Meta {
+    image: PathBuf,
+    #[serde(default)]
+    frame_tags: Vec<Tag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: Direction,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+impl Direction {
+    /// The frame indices of one loop through `from..=to`.
+    fn sequence(self, from: usize, to: usize) -> Vec<usize> {
+        let forward: Vec<usize> = (from..=to).collect();
+        let backward: Vec<usize> = forward.iter().rev().copied().collect();
+        // The ends are not shown twice in a row when the loop turns around.
+        let turn = |there: &[usize], back: &[usize]| {
+            let inner = back.len().saturating_sub(1);
+            there
+                .iter()
+                .chain(back.get(1..inner).unwrap_or_default())
+                .copied()
+                .collect()
+        };
+        match self {
+            Direction::Forward => forward,
+            Direction::Reverse => backward,
+            Direction::Pingpong => turn(&forward, &backward),
+            Direction::PingpongReverse => turn(&backward, &forward),
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<Export, SheetError> {
+    let text = fs::read_to_string(path).map_err(|err| SheetError::Io(path.into(), err))?;
+    let mut export: Export =
+        serde_json::from_str(&text).map_err(|err| SheetError::Json(path.into(), err))?;
+    if let Some(dir) = path.parent() {
+        export.meta.image = dir.join(&export.meta.image);
+    }
+    Ok(export)
+}
+
+/// The image an export refers to, resolved against the export's directory.
+pub fn image_path(path: &Path) -> Option<PathBuf> {
+    read(path).ok().map(|export| export.meta.image)
+}
+
+/// The animation a tag stands for.
+fn animation_name(tag: &str) -> String {
+    let name = tag.trim().to_lowercase().replace([' ', '-'], "_");
+    if let Some((_, animation)) = ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return animation.to_string();
+    }
+    for (prefix, replacement) in PREFIXES {
+        if let Some(direction) = name.strip_prefix(prefix) {
+            if ["n", "e", "s", "w"].contains(&direction) || *prefix == "walk_" {
+                return format!("{replacement}{direction}");
+            }
+        }
+    }
+    name
+}
+
+/// Reads an export and its image without validating them.
+pub fn open(path: &Path) -> Result<SpriteSheet, SheetError> {
+    let export = read(path)?;
+    let frames = match export.frames {
+        Frames::Array(frames) => frames,
+        Frames::Hash(frames) => frames
+            .into_iter()
+            .map(|(_, frame)| serde_json::from_value(frame))
+            .collect::<Result<_, _>>()
+            .map_err(|err| SheetError::Json(path.into(), err))?,
+    };
+    let mut image = image::open(&export.meta.image)
+        .map_err(|err| SheetError::Image(export.meta.image.clone(), err))?
+        .into_rgba8();
+
+    let mut problems: Vec<Problem> = frames
+        .iter()
+        .enumerate()
+        .filter(|(_, frame)| frame.rotated)
+        .map(|(index, _)| Problem::RotatedFrame(index))
+        .collect();
+
+    let sprite_size = frames
+        .first()
+        .map_or((0, 0), |frame| (frame.source_size.w, frame.source_size.h));
+    let refs = if frames.iter().any(|frame| frame.trimmed) {
+        let (refs, untrimmed) = untrim(&image, &frames);
+        image = untrimmed;
+        refs
+    } else {
+        frames
+            .iter()
+            .map(|frame| FrameRef::Rect {
+                x: frame.frame.x,
+                y: frame.frame.y,
+                width: frame.frame.w,
+                height: frame.frame.h,
+            })
+            .collect()
+    };
+
+    let mut animations = BTreeMap::new();
+    for tag in &export.meta.frame_tags {
+        if tag.from > tag.to || tag.to >= frames.len() {
+            problems.push(Problem::TagOutOfRange {
+                tag: tag.name.clone(),
+                to: tag.to,
+                frames: frames.len(),
+            });
+            continue;
+        }
+        let sequence = tag.direction.sequence(tag.from, tag.to);
+        let durations: Vec<Duration> = sequence
+            .iter()
+            .map(|&index| Duration::from_millis(frames[index].duration))
+            .collect();
+        let animation = Animation {
+            frames: sequence.iter().map(|&index| refs[index]).collect(),
+            interval: durations[0],
+            durations,
+        };
+        animations.insert(animation_name(&tag.name), animation);
+    }
+
+    Ok(SpriteSheet::new(image.into(), sprite_size, animations).with_problems(problems))
+}
+
+/// Copies every frame back into its untrimmed size, on a grid of the largest untrimmed size.
+fn untrim(image: &RgbaImage, frames: &[Frame]) -> (Vec<FrameRef>, RgbaImage) {
+    let width = frames
+        .iter()
+        .map(|frame| frame.source_size.w)
+        .max()
+        .unwrap_or(0);
+    let height = frames
+        .iter()
+        .map(|frame| frame.source_size.h)
+        .max()
+        .unwrap_or(0);
+    let columns = COLUMNS.min(frames.len() as u32);
+    let rows = (frames.len() as u32).div_ceil(COLUMNS);
+    let mut untrimmed = RgbaImage::new(columns * width, rows * height);
+
+    let refs = frames
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            let index = index as u32;
+            let (x, y) = ((index % COLUMNS) * width, (index / COLUMNS) * height);
+            let Rect {
+                x: left,
+                y: top,
+                w,
+                h,
+            } = frame.frame;
+            // The numbers come straight from the export, so a broken one must not overflow.
+            let inside = left
+                .checked_add(w)
+                .is_some_and(|right| right <= image.width())
+                && top
+                    .checked_add(h)
+                    .is_some_and(|bottom| bottom <= image.height());
+            if inside {
+                let offset = frame.sprite_source_size;
+                let sprite = image.view(left, top, w, h);
+                imageops::replace(
+                    &mut untrimmed,
+                    &*sprite,
+                    i64::from(x) + i64::from(offset.x),
+                    i64::from(y) + i64::from(offset.y),
+                );
+            }
+            FrameRef::Rect {
+                x,
+                y,
+                width: frame.source_size.w,
+                height: frame.source_size.h,
+            }
+        })
+        .collect();
+    (refs, untrimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unrolls_tag_directions() {
+        assert_eq!(Direction::Forward.sequence(2, 5), [2, 3, 4, 5]);
+        assert_eq!(Direction::Reverse.sequence(2, 5), [5, 4, 3, 2]);
+        assert_eq!(Direction::Pingpong.sequence(2, 5), [2, 3, 4, 5, 4, 3]);
+        assert_eq!(
+            Direction::PingpongReverse.sequence(2, 5),
+            [5, 4, 3, 2, 3, 4]
+        );
+        assert_eq!(Direction::Pingpong.sequence(1, 2), [1, 2]);
+        assert_eq!(Direction::Pingpong.sequence(7, 7), [7]);
+    }
+
+    #[test]
+    fn maps_tags_to_animations() {
+        assert_eq!(animation_name("idle"), "idle");
+        assert_eq!(animation_name(" Run-NE "), "run_ne");
+        assert_eq!(animation_name("Sleep"), "sleeping");
+        assert_eq!(animation_name("yawn"), "tired");
+        assert_eq!(animation_name("walk ne"), "run_ne");
+        assert_eq!(animation_name("wall-e"), "scratch_wall_e");
+        assert_eq!(animation_name("scratch_w"), "scratch_wall_w");
+        assert_eq!(animation_name("scratch_self"), "scratch_self");
+        assert_eq!(animation_name("wall_ne"), "wall_ne");
+    }
+
+    #[test]
+    fn reads_hash_and_array_exports() {
+        let frame = r#"{"frame": {"x": 0, "y": 0, "w": 4, "h": 4},
+            "spriteSourceSize": {"x": 0, "y": 0, "w": 4, "h": 4},
+            "sourceSize": {"w": 4, "h": 4}, "duration": 100}"#;
+        let meta = r#""meta": {"image": "cat.png",
+            "frameTags": [{"name": "idle", "from": 0, "to": 0, "direction": "pingpong"}]}"#;
+
+        let array: Export =
+            serde_json::from_str(&format!(r#"{{"frames": [{frame}], {meta}}}"#)).unwrap();
+        assert!(matches!(array.frames, Frames::Array(ref frames) if frames.len() == 1));
+        assert!(matches!(
+            array.meta.frame_tags[0].direction,
+            Direction::Pingpong
+        ));
+
+        let hash: Export = serde_json::from_str(&format!(
+            r#"{{"frames": {{"cat 0.ase": {frame}}}, {meta}}}"#
+        ))
+        .unwrap();
+        assert!(matches!(hash.frames, Frames::Hash(ref frames) if frames.len() == 1));
+    }
+
+    fn frame(frame: Rect, offset: (u32, u32), source_size: (u32, u32)) -> Frame {
+        Frame {
+            frame,
+            rotated: false,
+            trimmed: true,
+            sprite_source_size: Rect {
+                x: offset.0,
+                y: offset.1,
+                w: frame.w,
+                h: frame.h,
+            },
+            source_size: Size {
+                w: source_size.0,
+                h: source_size.1,
+            },
+            duration: 100,
+        }
+    }
+
+    #[test]
+    fn untrims_frames_onto_a_grid() {
+        let mut image = RgbaImage::new(4, 2);
+        image.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+        image.put_pixel(2, 0, image::Rgba([0, 255, 0, 255]));
+        let frames = [
+            frame(
+                Rect {
+                    x: 0,
+                    y: 0,
+                    w: 2,
+                    h: 2,
+                },
+                (1, 1),
+                (4, 4),
+            ),
+            frame(
+                Rect {
+                    x: 2,
+                    y: 0,
+                    w: 2,
+                    h: 2,
+                },
+                (0, 2),
+                (3, 4),
+            ),
+        ];
+
+        let (refs, untrimmed) = untrim(&image, &frames);
+        assert_eq!(untrimmed.dimensions(), (8, 4));
+        assert_eq!(
+            refs,
+            [
+                FrameRef::Rect {
+                    x: 0,
+                    y: 0,
+                    width: 4,
+                    height: 4
+                },
+                FrameRef::Rect {
+                    x: 4,
+                    y: 0,
+                    width: 3,
+                    height: 4
+                },
+            ]
+        );
+        assert_eq!(untrimmed.get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(untrimmed.get_pixel(4, 2).0, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn skips_frames_outside_the_image() {
+        let image = RgbaImage::new(4, 4);
+        let frames = [
+            frame(
+                Rect {
+                    x: u32::MAX,
+                    y: 0,
+                    w: 2,
+                    h: 2,
+                },
+                (0, 0),
+                (2, 2),
+            ),
+            frame(
+                Rect {
+                    x: 0,
+                    y: 3,
+                    w: 2,
+                    h: u32::MAX,
+                },
+                (0, 0),
+                (2, 2),
+            ),
+            frame(
+                Rect {
+                    x: 3,
+                    y: 0,
+                    w: 2,
+                    h: 2,
+                },
+                (0, 0),
+                (2, 2),
+            ),
+        ];
+
+        let (refs, untrimmed) = untrim(&image, &frames);
+        assert_eq!(refs.len(), 3);
+        assert!(untrimmed.pixels().all(|pixel| pixel.0 == [0; 4]));
+    }
+}
@@ -0,0 +1,376 @@
+//! Command line argument parsing.
+
+use std::{env, path::PathBuf, process, time::Duration};
+
+use image::Rgba;
+
+use crate::config::Overrides;
+use crate::logging::LogTarget;
+use crate::recolor::{Color, Variant};
+use crate::record::RecordOptions;
+use crate::sheet_tool::SheetOptions;
+
+#[cfg(target_os = "linux")]
+use crate::screensaver::ScreensaverWindow;
+
+const USAGE: &str = "\
+Usage: oneko-rust [OPTIONS]
+       oneko-rust record [RECORD OPTIONS] <OUTPUT>
+       oneko-rust sheet [--contact-sheet <PNG>] [MANIFEST]
+       oneko-rust ctl <CONTROL COMMAND>
+
+Commands:
+  record               Write an animated GIF (.gif) or APNG (.png) of a simulated session
+  sheet                Check a sprite sheet manifest (default: the built-in one) and
+                       optionally render a labeled contact sheet of its animations
+  ctl                  Send a command to the running cat over its control socket
+
+Options:
+  --backend <BACKEND>  Window backend to use: winit (default), x11, wayland or terminal
+  --screensaver        Run as a screensaver with a herd of cats
+  -root                Draw the screensaver on the root window
+  -window-id <ID>      Draw the screensaver into an existing window
+  --cats <COUNT>       Number of cats in the screensaver herd [default: 6], or to add to an
+                       instance that is already running [default: 1]
+  --debug              Log the cat's state on every step
+  --log <TARGET>       Where to log: stderr (default in debug builds), file (a file a day in
+                       $XDG_STATE_HOME/oneko-rust/logs) or journald; RUST_LOG picks what
+                       is logged
+  --config <PATH>      Config file, reloaded when it changes
+                       [default: $XDG_CONFIG_HOME/oneko-rust/config.toml]
+  -fg <COLOR>          Outline color, as #rrggbb
+  -bg <COLOR>          Fur color, as #rrggbb
+  -rv                  Swap the outline and fur colors
+  --variant <NAME>     Color variant: kuroneko
+  --character <NAME>   A character from the original oneko: neko, tora, dog, sakura or
+                       tomoyo, read from its XBM files in
+                       $XDG_DATA_HOME/oneko-rust/characters/<NAME>
+  --new-instance       Start another instance even if one is running already, instead of
+                       passing it --cats and the theme options and exiting
+  -h, --help           Print this help
+
+Record options:
+  --duration <SECONDS>  Length of the recording [default: 20]
+  --size <WxH>          Size of the recorded area [default: 320x240]
+  --background <COLOR>  #rrggbb background color or transparent [default: transparent]
+  --debug               Draw the cat's state, follow radius, target and wall over the frames
+
+Control commands (ID is a cat id from `ctl state`, all cats without one):
+  pause | resume        Freeze every cat in place, or let them go again
+  summon <X> <Y> [ID]   Send cats to a point on their monitor until the target changes
+  sleep [ID] | wake [ID]
+                        Put cats to sleep until woken, or wake them
+  spawn [COUNT]         Add cats, where the backend can show more than one
+  remove [ID]           Take cats away, where the backend can show more than one
+  target pointer | roam | <X> <Y>
+                        What the cats follow
+  state                 Print every cat's state as JSON
+  subscribe             Print the cats' state changes, clicks, wall contacts, spawns and
+                        despawns as JSON lines as they happen
+  send <JSON>           Send a raw request
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Cross-platform windows through winit and softbuffer.
+    Winit,
+    /// A single override-redirect window driven directly through x11rb.
+    #[cfg(target_os = "linux")]
+    X11,
+    /// A wlr-layer-shell overlay covering a whole output.
+    #[cfg(target_os = "linux")]
+    Wayland,
+    /// Half-block truecolor cells in the current terminal.
+    Terminal,
+}
+
+pub enum Command {
+    /// Run the cat on the desktop.
+    Run,
+    Record(RecordOptions),
+    Sheet(SheetOptions),
+    /// Send a request to the running cat's control socket.
+    #[cfg(target_os = "linux")]
+    Ctl(serde_json::Value),
+}
+
+pub struct Args {
+    pub command: Command,
+    pub backend: Backend,
+    #[cfg(target_os = "linux")]
+    pub screensaver: Option<ScreensaverWindow>,
+    /// How many cats to show, if given.
+    pub cats: Option<usize>,
+    pub config: Option<PathBuf>,
+    pub overrides: Overrides,
+    /// Log every step of the cat.
+    pub debug: bool,
+    pub log: Option<LogTarget>,
+    /// Run alongside an instance that is already running instead of handing it the arguments.
+    pub new_instance: bool,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        let mut args = Self {
+            command: Command::Run,
+            backend: Backend::Winit,
+            #[cfg(target_os = "linux")]
+            screensaver: None,
+            cats: None,
+            config: None,
+            overrides: Overrides::default(),
+            debug: false,
+            log: None,
+            new_instance: false,
+        };
+
+        let mut iter = env::args().skip(1).peekable();
+        if iter.next_if(|arg| arg == "record").is_some() {
+            let options = parse_record(&mut iter);
+            args.debug = options.debug;
+            args.command = Command::Record(options);
+            return args;
+        }
+        if iter.next_if(|arg| arg == "sheet").is_some() {
+            args.command = Command::Sheet(parse_sheet(&mut iter));
+            return args;
+        }
+        #[cfg(target_os = "linux")]
+        if iter.next_if(|arg| arg == "ctl").is_some() {
+            args.command = Command::Ctl(parse_ctl(iter.collect()));
+            return args;
+        }
+
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--backend" => {
+                    args.backend = match value(&mut iter, &arg).as_str() {
+                        "winit" => Backend::Winit,
+                        #[cfg(target_os = "linux")]
+                        "x11" => Backend::X11,
+                        #[cfg(target_os = "linux")]
+                        "wayland" => Backend::Wayland,
+                        "terminal" => Backend::Terminal,
+                        other => usage_error(&format!("Unknown backend: {other}")),
+                    };
+                }
+                #[cfg(target_os = "linux")]
+                "--screensaver" => {
+                    args.screensaver = args.screensaver.or(Some(
+                        xscreensaver_window().unwrap_or(ScreensaverWindow::Standalone),
+                    ));
+                }
+                // XScreenSaver passes `-root` but expects hacks to prefer the window it exports.
+                #[cfg(target_os = "linux")]
+                "-root" | "--root" => {
+                    args.screensaver =
+                        Some(xscreensaver_window().unwrap_or(ScreensaverWindow::Root));
+                }
+                #[cfg(target_os = "linux")]
+                "-window-id" | "--window-id" => {
+                    let id = value(&mut iter, &arg);
+                    let window = parse_window_id(&id)
+                        .unwrap_or_else(|| usage_error(&format!("Invalid window id: {id}")));
+                    args.screensaver = Some(ScreensaverWindow::Existing(window));
+                }
+                "--cats" => {
+                    let count = value(&mut iter, &arg);
+                    args.cats =
+                        Some(count.parse().unwrap_or_else(|_| {
+                            usage_error(&format!("Invalid cat count: {count}"))
+                        }));
+                }
+                "--config" => args.config = Some(value(&mut iter, &arg).into()),
+                "--debug" => args.debug = true,
+                "--log" => {
+                    let name = value(&mut iter, &arg);
+                    args.log =
+                        Some(LogTarget::parse(&name).unwrap_or_else(|| {
+                            usage_error(&format!("Unknown log target: {name}"))
+                        }));
+                }
+                "--new-instance" => args.new_instance = true,
+                "-fg" | "--fg" => args.overrides.colors.fg = Some(color(&value(&mut iter, &arg))),
+                "-bg" | "--bg" => args.overrides.colors.bg = Some(color(&value(&mut iter, &arg))),
+                "-rv" | "--rv" => args.overrides.colors.reverse = true,
+                "--character" => args.overrides.character = Some(value(&mut iter, &arg)),
+                "--variant" => {
+                    let name = value(&mut iter, &arg);
+                    args.overrides.colors.variant = Some(
+                        Variant::parse(&name)
+                            .unwrap_or_else(|| usage_error(&format!("Unknown variant: {name}"))),
+                    );
+                }
+                "-h" | "--help" => {
+                    print!("{USAGE}");
+                    process::exit(0);
+                }
+                other => usage_error(&format!("Unknown argument: {other}")),
+            }
+        }
+
+        args
+    }
+}
+
+fn parse_record(iter: &mut impl Iterator<Item = String>) -> RecordOptions {
+    let mut output = None;
+    let mut options = RecordOptions {
+        output: PathBuf::new(),
+        duration: Duration::from_secs(20),
+        size: (320, 240),
+        background: None,
+        debug: false,
+    };
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--duration" => {
+                let seconds = value(iter, &arg);
+                options.duration = seconds
+                    .parse()
+                    .ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .filter(|duration| !duration.is_zero())
+                    .unwrap_or_else(|| usage_error(&format!("Invalid duration: {seconds}")));
+            }
+            "--size" => {
+                let size = value(iter, &arg);
+                options.size = size
+                    .split_once('x')
+                    .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                    .filter(|&(width, height)| width > 0 && height > 0)
+                    .unwrap_or_else(|| usage_error(&format!("Invalid size: {size}")));
+            }
+            "--background" => {
+                let color = value(iter, &arg);
+                options.background = match color.as_str() {
+                    "transparent" => None,
+                    _ => Some(
+                        parse_color(&color)
+                            .unwrap_or_else(|| usage_error(&format!("Invalid color: {color}"))),
+                    ),
+                };
+            }
+            "--debug" => options.debug = true,
+            "-h" | "--help" => {
+                print!("{USAGE}");
+                process::exit(0);
+            }
+            _ if output.is_none() && !arg.starts_with('-') => output = Some(PathBuf::from(arg)),
+            other => usage_error(&format!("Unknown argument: {other}")),
+        }
+    }
+
+    options.output = output.unwrap_or_else(|| usage_error("record needs an output file"));
+    options
+}
+
+fn parse_sheet(iter: &mut impl Iterator<Item = String>) -> SheetOptions {
+    let mut options = SheetOptions {
+        manifest: None,
+        contact_sheet: None,
+    };
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--contact-sheet" => options.contact_sheet = Some(value(iter, &arg).into()),
+            "-h" | "--help" => {
+                print!("{USAGE}");
+                process::exit(0);
+            }
+            _ if options.manifest.is_none() && !arg.starts_with('-') => {
+                options.manifest = Some(PathBuf::from(arg))
+            }
+            other => usage_error(&format!("Unknown argument: {other}")),
+        }
+    }
+
+    options
+}
+
+/// Turns `ctl` arguments into a control socket request.
+#[cfg(target_os = "linux")]
+fn parse_ctl(args: Vec<String>) -> serde_json::Value {
+    use serde_json::json;
+
+    let number = |arg: &str| -> i64 {
+        arg.parse()
+            .unwrap_or_else(|_| usage_error(&format!("Invalid number: {arg}")))
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let request = match args.as_slice() {
+        ["pause"] => json!({ "command": "pause" }),
+        ["resume"] => json!({ "command": "resume" }),
+        ["state"] => json!({ "command": "state" }),
+        ["subscribe"] => json!({ "command": "subscribe" }),
+        ["summon", x, y, cat @ ..] if cat.len() <= 1 => json!({
+            "command": "summon",
+            "x": number(x),
+            "y": number(y),
+            "cat": cat.first().map(|cat| number(cat)),
+        }),
+        [command @ ("sleep" | "wake" | "remove"), cat @ ..] if cat.len() <= 1 => json!({
+            "command": command,
+            "cat": cat.first().map(|cat| number(cat)),
+        }),
+        ["spawn"] => json!({ "command": "spawn" }),
+        ["spawn", count] => json!({ "command": "spawn", "count": number(count) }),
+        ["target", source @ ("pointer" | "roam")] => {
+            json!({ "command": "target", "source": source })
+        }
+        ["target", x, y] => json!({
+            "command": "target",
+            "source": { "point": [number(x), number(y)] },
+        }),
+        ["send", request] => serde_json::from_str(request)
+            .unwrap_or_else(|err| usage_error(&format!("Invalid request: {err}"))),
+        ["-h" | "--help"] => {
+            print!("{USAGE}");
+            process::exit(0);
+        }
+        [] => usage_error("ctl needs a command"),
+        _ => usage_error(&format!("Unknown control command: {}", args.join(" "))),
+    };
+    request
+}
+
+/// Parses an opaque `#rrggbb` color.
+pub fn parse_color(color: &str) -> Option<Rgba<u8>> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+    Some(Rgba([channel(0)?, channel(2)?, channel(4)?, 255]))
+}
+
+fn color(color: &str) -> Color {
+    Color::try_from(color.to_string()).unwrap_or_else(|err| usage_error(&err))
+}
+
+fn value(iter: &mut impl Iterator<Item = String>, flag: &str) -> String {
+    iter.next()
+        .unwrap_or_else(|| usage_error(&format!("{flag} needs a value")))
+}
+
+#[cfg(target_os = "linux")]
+fn xscreensaver_window() -> Option<ScreensaverWindow> {
+    let id = env::var("XSCREENSAVER_WINDOW").ok()?;
+    parse_window_id(&id).map(ScreensaverWindow::Existing)
+}
+
+/// Parses an X11 window id, either in decimal or as `0x`-prefixed hex.
+#[cfg(target_os = "linux")]
+fn parse_window_id(id: &str) -> Option<u32> {
+    match id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => id.parse().ok(),
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{message}\n\n{USAGE}");
+    process::exit(2);
+}
@@ -0,0 +1,309 @@
+//! The optional config file, and turning it into a [`Theme`].
+//!
+//! ```toml
+//! # ~/.config/oneko-rust/config.toml
+//! sprite_sheet = "themes/tora.toml"  # a manifest or Aseprite .json export, relative to this file
+//! character = "dog"                  # or a classic oneko character from its XBM files in
+//!                                    # ~/.local/share/oneko-rust/characters/dog: neko, tora,
+//!                                    # dog, sakura or tomoyo
+//! speed = 10                         # pixels per step
+//! follow_distance = 60               # pixels
+//! scale = 3                          # leave out to follow the monitor's scale factor
+//! tints = ["#ffd0d0", "#d0e0ff"]     # handed out to cats in turn
+//! exit_animation = "curl_up"         # on SIGTERM or SIGINT: run_off (default), curl_up or none
+//!
+//! workspaces = "follow"              # virtual desktops: wm (default), sticky, follow or
+//!                                    # per_workspace
+//!
+//! [spawn]                            # where new cats show up
+//! at = "edge"                        # random (default), cursor, edge, top_left, top_right,
+//!                                    # bottom_left, bottom_right or a fixed [x, y]
+//! monitor = "DP-1"                   # instead of the primary monitor
+//!
+//! [trail]                            # paw prints behind the running cat
+//! spacing = 12                       # pixels run between two prints
+//! lifetime = 3                       # seconds until a print has faded away
+//!
+//! [[accessories]]
+//! manifest = "accessories/santa_hat.toml"
+//! dates = "12-01..12-26"             # leave out to always wear it
+//!
+//! [colors]
+//! variant = "kuroneko"               # or pick your own ramp with fg, bg and reverse
+//! palette = { "#ff00ff" = "#ff0000" } # exact replacements
+//! ```
+
+use std::{
+    env, fmt, fs, io,
+    path::{self, Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::accessory::{self, Accessory, DateRange};
+use crate::oneko::{self, builtin_sprite_sheet, ExitAnimation, Theme, FOLLOW_DISTANCE, SPEED};
+use crate::recolor::{Color, Recolor};
+use crate::spawn::SpawnConfig;
+use crate::sprite_sheet::{SheetError, SpriteSheet};
+use crate::trail::{self, TrailConfig};
+use crate::workspace::Workspaces;
+use crate::xbm;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// A sprite sheet manifest or Aseprite export to use instead of the built-in cat.
+    pub sprite_sheet: Option<PathBuf>,
+    /// A character from the original oneko, taking precedence over `sprite_sheet`.
+    pub character: Option<String>,
+    pub speed: f64,
+    pub follow_distance: f64,
+    /// A fixed scale, ignoring the monitor's. Fractional values are allowed.
+    pub scale: Option<f64>,
+    pub tints: Vec<Color>,
+    pub colors: Recolor,
+    pub accessories: Vec<AccessoryConfig>,
+    pub trail: Option<TrailConfig>,
+    /// What the cats do when asked to quit.
+    pub exit_animation: ExitAnimation,
+    pub spawn: SpawnConfig,
+    /// Which virtual desktops the cats show up on.
+    pub workspaces: Workspaces,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessoryConfig {
+    /// An accessory manifest, relative to the config.
+    pub manifest: PathBuf,
+    /// When to wear it. Always, if left out.
+    pub dates: Option<DateRange>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sprite_sheet: None,
+            character: None,
+            speed: SPEED,
+            follow_distance: FOLLOW_DISTANCE,
+            scale: None,
+            tints: Vec::new(),
+            colors: Recolor::default(),
+            accessories: Vec::new(),
+            trail: None,
+            exit_animation: ExitAnimation::default(),
+            spawn: SpawnConfig::default(),
+            workspaces: Workspaces::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(PathBuf, &'static str),
+    Sheet(SheetError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "Error reading {}: {err}", path.display()),
+            ConfigError::Parse(path, err) => write!(f, "Error parsing {}: {err}", path.display()),
+            ConfigError::Invalid(path, message) => write!(f, "{}: {message}", path.display()),
+            ConfigError::Sheet(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<SheetError> for ConfigError {
+    fn from(err: SheetError) -> Self {
+        ConfigError::Sheet(err)
+    }
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/oneko-rust/config.toml`, falling back to `~/.config` and `%APPDATA%`.
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(dir.join("oneko-rust").join("config.toml"))
+    }
+
+    /// Reads the config, treating a missing file as an empty one. The sprite sheet path is
+    /// resolved against the config's directory.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(ConfigError::Io(path.into(), err)),
+        };
+        let mut config: Self =
+            toml::from_str(&text).map_err(|err| ConfigError::Parse(path.into(), err))?;
+
+        if !(config.speed.is_finite() && config.speed > 0.0) {
+            return Err(ConfigError::Invalid(path.into(), "speed must be positive"));
+        }
+        if !(config.follow_distance.is_finite() && config.follow_distance >= 0.0) {
+            return Err(ConfigError::Invalid(
+                path.into(),
+                "follow_distance must not be negative",
+            ));
+        }
+
+        if config
+            .scale
+            .is_some_and(|scale| !(scale.is_finite() && scale > 0.0))
+        {
+            return Err(ConfigError::Invalid(path.into(), "scale must be positive"));
+        }
+        if let Some(trail) = config.trail {
+            if !(trail.spacing.is_finite() && trail.spacing > 0.0) {
+                return Err(ConfigError::Invalid(
+                    path.into(),
+                    "trail spacing must be positive",
+                ));
+            }
+            if !(trail.lifetime.is_finite() && trail.lifetime > 0.0) {
+                return Err(ConfigError::Invalid(
+                    path.into(),
+                    "trail lifetime must be positive",
+                ));
+            }
+        }
+
+        if let Some(dir) = path.parent() {
+            if let Some(sheet) = &mut config.sprite_sheet {
+                *sheet = dir.join(&*sheet);
+            }
+            for accessory in &mut config.accessories {
+                accessory.manifest = dir.join(&accessory.manifest);
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn theme(&self) -> Result<Theme, ConfigError> {
+        let mut sheet = match (&self.character, &self.sprite_sheet) {
+            (Some(character), _) => xbm::load_character(character)?,
+            (None, Some(path)) => SpriteSheet::load(path)?,
+            (None, None) => builtin_sprite_sheet(),
+        };
+        self.colors.apply(sheet.image_mut());
+
+        let today = accessory::today();
+        for config in &self.accessories {
+            if config.dates.is_none_or(|dates| dates.contains(today)) {
+                Accessory::load(&config.manifest)?.composite(&mut sheet);
+            }
+        }
+        Ok(Theme::new(sheet)
+            .with_speed(self.speed)
+            .with_follow_distance(self.follow_distance)
+            .with_scale(self.scale)
+            .with_tints(self.tints.clone())
+            .with_trail(self.trail)
+            .with_exit_animation(self.exit_animation)
+            .with_spawn(self.spawn.clone())
+            .with_workspaces(self.workspaces))
+    }
+
+    /// Every file the theme was built from, so a watcher knows what to look out for.
+    pub fn files(&self, path: &Path) -> Vec<PathBuf> {
+        let mut files = vec![path.to_path_buf()];
+        match (&self.character, &self.sprite_sheet) {
+            (Some(character), _) => files.extend(xbm::character_files(character)),
+            (None, Some(manifest)) => {
+                files.push(manifest.clone());
+                files.extend(SpriteSheet::image_path(manifest));
+            }
+            (None, None) => (),
+        }
+        for accessory in &self.accessories {
+            files.push(accessory.manifest.clone());
+            files.extend(Accessory::image_path(&accessory.manifest));
+        }
+        files
+    }
+}
+
+/// Where the current theme was loaded from, so that reloads and date changes load it from there
+/// again.
+#[derive(Debug, Clone, Default)]
+pub struct Source {
+    pub path: Option<PathBuf>,
+    pub overrides: Overrides,
+}
+
+static SOURCE: Mutex<Option<Source>> = Mutex::new(None);
+
+/// Where the current theme was loaded from.
+pub fn source() -> Source {
+    SOURCE.lock().unwrap().clone().unwrap_or_default()
+}
+
+/// Remembers the config at `path` with `overrides` over it as where the theme comes from, whether
+/// or not it loads.
+pub fn set_source(path: Option<&Path>, overrides: &Overrides) {
+    // Relative paths would leave nothing to watch for files in the working directory.
+    let path = path.map(|path| path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+    *SOURCE.lock().unwrap() = Some(Source {
+        path,
+        overrides: overrides.clone(),
+    });
+}
+
+/// Settings given on the command line, which win over the config file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Overrides {
+    pub colors: Recolor,
+    pub character: Option<String>,
+}
+
+/// Loads the config at `path`, or the defaults without one, and layers the command line
+/// `overrides` over it.
+pub fn load_theme(path: Option<&Path>, overrides: &Overrides) -> Result<Theme, ConfigError> {
+    let mut config = match path {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    config.colors.merge(&overrides.colors);
+    if let Some(character) = &overrides.character {
+        config.character = Some(character.clone());
+    }
+    config.theme()
+}
+
+/// Makes the theme from [`load_theme`] the theme of every cat and its config the [`source`].
+/// Anything wrong with it leaves both as they were.
+pub fn switch(path: Option<&Path>, overrides: &Overrides) -> Result<(), ConfigError> {
+    let theme = load_theme(path, overrides)?;
+    if theme.trail().is_some() && !trail::drawn() {
+        tracing::warn!(
+            "Only the Wayland backend, the screensaver and recordings draw a [trail], so there \
+             are no paw prints here"
+        );
+    }
+    oneko::set_theme(theme);
+    set_source(path, overrides);
+    Ok(())
+}
+
+/// [`switch`]es to the theme from `path`, logging anything wrong with it.
+pub fn apply(path: Option<&Path>, overrides: &Overrides) -> bool {
+    match switch(path, overrides) {
+        Ok(()) => true,
+        Err(err) => {
+            tracing::error!("{err}");
+            false
+        }
+    }
+}
@@ -0,0 +1,660 @@
+//! Scripting a running cat over a Unix socket.
+//!
+//! While the cat runs it listens on `$XDG_RUNTIME_DIR/oneko.sock`, or `/tmp/oneko-<uid>/oneko.sock`
+//! without a runtime dir, for one JSON request per line and answers each with one JSON line, such
+//! as
+//!
+//! ```text
+//! {"command": "summon", "x": 200, "y": 100}
+//! {"ok": true}
+//! ```
+//!
+//! The commands are `pause`, `resume`, `summon` (`x`, `y`), `sleep`, `wake`, `spawn` (`count`),
+//! `remove`, `target` (`source`: `"pointer"`, `"roam"` or `{"point": [x, y]}`), `state` and
+//! `subscribe`.
+//! `summon`, `sleep`, `wake` and `remove` take an optional `cat` id from `state` and otherwise
+//! apply to every cat. Coordinates are relative to the monitor the cat is on.
+//!
+//! `subscribe` keeps the connection open after its answer and streams [`Event`]s down it, one
+//! JSON line each, starting with the current state of every cat:
+//!
+//! ```text
+//! {"event": "state", "time": 1760000000000, "cat": 1, "from": "tired", "to": "sleeping", "position": [68, 48]}
+//! ```
+//!
+//! `time` is in milliseconds since the Unix epoch. Status bars can follow it with something like
+//! `oneko-rust ctl subscribe | jq --unbuffered -r 'select(.event == "state") | .to'`.
+//!
+//! A second `oneko-rust` started while one is running sends it a `launch` request with its
+//! `--cats` (`cats`) and, when any were given, its config and theme options (`config`,
+//! `overrides`), then exits. A plain second launch asks for one more cat.
+//!
+//! `oneko-rust ctl` sends them from the command line, which is handy for window manager key
+//! bindings.
+//!
+//! Requests only queue up orders here. Each cat picks up its orders on its next step, and
+//! backends that can show more than one cat pick up spawns and removals on their next update.
+
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, Overrides};
+use crate::persist;
+
+#[cfg(target_os = "linux")]
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::OnceLock,
+    thread,
+};
+
+/// Where a cat heads when it has not been summoned anywhere.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetSource {
+    /// Whatever the backend follows: the pointer, or a roaming spot in screensavers.
+    #[default]
+    Pointer,
+    /// A random spot that moves every so often.
+    Roam,
+    Point((i32, i32)),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Request {
+    Pause,
+    Resume,
+    Summon {
+        x: i32,
+        y: i32,
+        cat: Option<u64>,
+    },
+    Sleep {
+        cat: Option<u64>,
+    },
+    Wake {
+        cat: Option<u64>,
+    },
+    Spawn {
+        #[serde(default = "one")]
+        count: usize,
+    },
+    Remove {
+        cat: Option<u64>,
+    },
+    Target {
+        source: TargetSource,
+    },
+    State,
+    Subscribe,
+    /// Sent by a second instance instead of starting up.
+    Launch {
+        #[serde(default)]
+        cats: usize,
+        config: Option<PathBuf>,
+        /// Loads `config` with these over it as the new theme, when given.
+        overrides: Option<Overrides>,
+    },
+}
+
+fn one() -> usize {
+    1
+}
+
+/// Something a single cat has been told to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Summon((i32, i32)),
+    /// Forget about being summoned and follow the target source again.
+    Release,
+    Sleep,
+    Wake,
+    Remove,
+}
+
+/// Something that happened to a cat, as streamed to subscribers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A cat took its first step.
+    Spawn {
+        cat: u64,
+        position: (i32, i32),
+    },
+    /// A cat went away, last seen at `position`.
+    Despawn {
+        cat: u64,
+        position: Option<(i32, i32)>,
+    },
+    /// A cat started playing another animation. `from` is missing in the states sent when
+    /// subscribing.
+    State {
+        cat: u64,
+        from: Option<&'static str>,
+        to: &'static str,
+        position: (i32, i32),
+    },
+    /// A cat ran into the edge of its monitor while chasing the target.
+    Wall {
+        cat: u64,
+        wall: &'static str,
+        position: (i32, i32),
+    },
+    Click {
+        cat: u64,
+        position: (i32, i32),
+    },
+}
+
+/// How a cat was doing on its last step, as reported by `state`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CatState {
+    pub id: u64,
+    pub state: &'static str,
+    pub frame_count: u32,
+    pub position: (i32, i32),
+    pub target: (i32, i32),
+    pub asleep: bool,
+    pub summoned: Option<(i32, i32)>,
+}
+
+#[derive(Default)]
+struct Control {
+    paused: bool,
+    target: TargetSource,
+    /// Whether the running backend can show more than one cat.
+    herd: bool,
+    spawns: usize,
+    orders: BTreeMap<u64, Vec<Order>>,
+    cats: BTreeMap<u64, Option<CatState>>,
+    /// Event streams of `subscribe` connections, dropped once the other end goes away.
+    subscribers: Vec<Sender<String>>,
+}
+
+impl Control {
+    fn broadcast(&mut self, event: &Event) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let line = event_line(event);
+        self.subscribers
+            .retain(|subscriber| subscriber.send(line.clone()).is_ok());
+    }
+}
+
+static CONTROL: Mutex<Option<Control>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+/// The socket this process listens on, to remove on the way out.
+#[cfg(target_os = "linux")]
+static BOUND: OnceLock<PathBuf> = OnceLock::new();
+
+fn with<T>(f: impl FnOnce(&mut Control) -> T) -> T {
+    f(CONTROL.lock().unwrap().get_or_insert_with(Control::default))
+}
+
+/// Hands out an id for a new cat and starts taking orders for it.
+pub fn register() -> u64 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    with(|control| control.cats.insert(id, None));
+    id
+}
+
+/// Stops taking orders for a cat that is gone.
+pub fn unregister(id: u64) {
+    with(|control| {
+        let last = control.cats.remove(&id).flatten();
+        control.orders.remove(&id);
+        control.broadcast(&Event::Despawn {
+            cat: id,
+            position: last.map(|state| state.position),
+        });
+    });
+}
+
+/// An event as a line of the event stream, stamped with the time in milliseconds.
+fn event_line(event: &Event) -> String {
+    let mut line = serde_json::to_value(event).expect("Error serializing event");
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    line.as_object_mut()
+        .expect("Events serialize to objects")
+        .insert("time".into(), time.into());
+    line.to_string()
+}
+
+/// Records how a cat is doing, for `state`, announcing it to subscribers on its first step.
+pub fn report(state: CatState) {
+    with(|control| {
+        let Some(slot) = control.cats.get_mut(&state.id) else {
+            return;
+        };
+        let spawned = slot.is_none();
+        let event = Event::Spawn {
+            cat: state.id,
+            position: state.position,
+        };
+        *slot = Some(state);
+        if spawned {
+            control.broadcast(&event);
+        }
+    });
+}
+
+/// Tells subscribers about something that happened to a cat.
+pub fn emit(event: Event) {
+    with(|control| control.broadcast(&event));
+}
+
+/// Starts a new event stream, primed with the current state of every cat.
+fn subscribe() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    with(|control| {
+        // Only the new subscriber needs the snapshot, the others have been following along.
+        for state in control.cats.values().flatten() {
+            let event = Event::State {
+                cat: state.id,
+                from: None,
+                to: state.state,
+                position: state.position,
+            };
+            let _ = sender.send(event_line(&event));
+        }
+        control.subscribers.push(sender);
+    });
+    receiver
+}
+
+/// Takes the orders waiting for a cat.
+pub fn orders(id: u64) -> Vec<Order> {
+    with(|control| control.orders.remove(&id).unwrap_or_default())
+}
+
+pub fn paused() -> bool {
+    with(|control| control.paused)
+}
+
+pub fn target_source() -> TargetSource {
+    with(|control| control.target)
+}
+
+/// Says that the running backend shows any number of cats and will act on `spawns`.
+pub fn allow_herd() {
+    with(|control| control.herd = true);
+}
+
+/// How many cats have been asked for since the last call.
+pub fn take_spawns() -> usize {
+    with(|control| std::mem::take(&mut control.spawns))
+}
+
+fn send_orders(control: &mut Control, cat: Option<u64>, order: Order) -> Result<(), String> {
+    let ids: Vec<u64> = match cat {
+        Some(id) if control.cats.contains_key(&id) => vec![id],
+        Some(id) => return Err(format!("no cat with id {id}")),
+        None => control.cats.keys().copied().collect(),
+    };
+    for id in ids {
+        control.orders.entry(id).or_default().push(order);
+    }
+    Ok(())
+}
+
+/// Carries out a request, returning the JSON answer.
+pub fn handle(request: Request) -> serde_json::Value {
+    // Loading a theme takes a while, so it happens before the cats' bookkeeping is locked.
+    if let Request::Launch {
+        config,
+        overrides: Some(overrides),
+        ..
+    } = &request
+    {
+        if let Err(err) = config::switch(config.as_deref(), overrides) {
+            return serde_json::json!({ "ok": false, "error": err.to_string() });
+        }
+        // Reloads follow the new config from now on.
+        #[cfg(target_os = "linux")]
+        crate::reload::update_watches();
+    }
+
+    let result = with(|control| -> Result<serde_json::Value, String> {
+        match request {
+            Request::Pause => control.paused = true,
+            Request::Resume => control.paused = false,
+            Request::Summon { x, y, cat } => send_orders(control, cat, Order::Summon((x, y)))?,
+            Request::Sleep { cat } => send_orders(control, cat, Order::Sleep)?,
+            Request::Wake { cat } => send_orders(control, cat, Order::Wake)?,
+            Request::Spawn { count } => {
+                if !control.herd {
+                    return Err("this backend shows a single cat".into());
+                }
+                control.spawns += count;
+            }
+            Request::Remove { cat } => {
+                if !control.herd {
+                    return Err("this backend shows a single cat".into());
+                }
+                send_orders(control, cat, Order::Remove)?;
+            }
+            Request::Target { source } => {
+                control.target = source;
+                send_orders(control, None, Order::Release)?;
+            }
+            Request::State => {
+                let cats: Vec<&CatState> = control.cats.values().flatten().collect();
+                return Ok(serde_json::json!({
+                    "ok": true,
+                    "paused": control.paused,
+                    "target": control.target,
+                    "cats": cats,
+                    "stats": persist::stats(),
+                }));
+            }
+            Request::Subscribe => return Err("subscribe needs a connection of its own".into()),
+            Request::Launch { cats: 0, .. } => (),
+            Request::Launch { cats, .. } => {
+                if !control.herd {
+                    return Err("this backend shows a single cat".into());
+                }
+                control.spawns += cats;
+            }
+        }
+        Ok(serde_json::json!({ "ok": true }))
+    });
+    result.unwrap_or_else(|error| serde_json::json!({ "ok": false, "error": error }))
+}
+
+/// `$XDG_RUNTIME_DIR`, or a directory of this user's own in `/tmp` without a runtime dir.
+#[cfg(target_os = "linux")]
+fn socket_dir() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => env::temp_dir().join(format!("oneko-{}", uid())),
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn socket_path() -> PathBuf {
+    socket_dir().join("oneko.sock")
+}
+
+#[cfg(target_os = "linux")]
+fn uid() -> u32 {
+    // SAFETY: getuid has no preconditions and cannot fail.
+    unsafe { libc::getuid() }
+}
+
+/// Makes sure the socket's directory belongs to this user and nobody else can get into it, making
+/// it first if asked to. Anyone can make files in `/tmp`, so another user could have put a socket
+/// of their own there to listen in or answer for the cat.
+#[cfg(target_os = "linux")]
+fn check_socket_dir(dir: &Path, create: bool) -> io::Result<()> {
+    if create {
+        match fs::DirBuilder::new().mode(0o700).create(dir) {
+            Err(err) if err.kind() != io::ErrorKind::AlreadyExists => return Err(err),
+            _ => (),
+        }
+    }
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid() || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not a directory only you can get into", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Starts answering requests on the control socket on a background thread.
+#[cfg(target_os = "linux")]
+pub fn serve() {
+    if let Err(err) = check_socket_dir(&socket_dir(), true) {
+        tracing::warn!("Not taking commands: {err}");
+        return;
+    }
+    let path = socket_path();
+    if UnixStream::connect(&path).is_ok() {
+        tracing::warn!(
+            "Another cat is already listening on {}, not taking commands",
+            path.display()
+        );
+        return;
+    }
+    // Nobody answered, so whatever is there was left behind by a cat that did not exit cleanly.
+    let _ = fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => {
+            let _ = BOUND.set(path);
+            listener
+        }
+        Err(err) => {
+            tracing::warn!("Error listening on {}: {err}", path.display());
+            return;
+        }
+    };
+
+    thread::Builder::new()
+        .name("control".into())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = thread::Builder::new()
+                    .name("control client".into())
+                    .spawn(move || answer(stream));
+            }
+        })
+        .expect("Error spawning control thread");
+}
+
+/// Removes the socket, if this process is the one listening on it.
+#[cfg(target_os = "linux")]
+pub fn close() {
+    if let Some(path) = BOUND.get() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn answer(stream: UnixStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str(&line) {
+            Ok(Request::Subscribe) => {
+                writeln!(writer, "{}", serde_json::json!({ "ok": true }))?;
+                for event in subscribe() {
+                    writeln!(writer, "{event}")?;
+                }
+                return Ok(());
+            }
+            Ok(request) => handle(request),
+            Err(err) => serde_json::json!({ "ok": false, "error": err.to_string() }),
+        };
+        writeln!(writer, "{response}")?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn connect() -> io::Result<UnixStream> {
+    check_socket_dir(&socket_dir(), false)
+        .map_err(|err| io::Error::new(err.kind(), format!("Not sending commands: {err}")))?;
+    let path = socket_path();
+    UnixStream::connect(&path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("No cat is listening on {}: {err}", path.display()),
+        )
+    })
+}
+
+/// Sends one request to the running cat and returns its answer.
+#[cfg(target_os = "linux")]
+pub fn send(request: &serde_json::Value) -> io::Result<serde_json::Value> {
+    let mut stream = connect()?;
+    writeln!(stream, "{request}")?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    serde_json::from_str(&line).map_err(io::Error::other)
+}
+
+/// Subscribes to the running cat's events and prints each one as it comes, until the cat exits.
+#[cfg(target_os = "linux")]
+pub fn follow_events() -> io::Result<()> {
+    let mut stream = connect()?;
+    writeln!(stream, "{}", serde_json::json!({ "command": "subscribe" }))?;
+    let mut lines = BufReader::new(stream).lines();
+    // The first line only acknowledges the subscription.
+    lines.next().transpose()?;
+    let mut stdout = io::stdout().lock();
+    for line in lines {
+        writeln!(stdout, "{}", line?)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(json: &str) -> Result<Request, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    fn line(line: &str) -> serde_json::Value {
+        serde_json::from_str(line).unwrap()
+    }
+
+    #[test]
+    fn parses_requests() {
+        assert!(matches!(
+            request(r#"{"command": "summon", "x": 10, "y": -5}"#),
+            Ok(Request::Summon {
+                x: 10,
+                y: -5,
+                cat: None
+            })
+        ));
+        assert!(matches!(
+            request(r#"{"command": "sleep", "cat": 3}"#),
+            Ok(Request::Sleep { cat: Some(3) })
+        ));
+        assert!(matches!(
+            request(r#"{"command": "spawn"}"#),
+            Ok(Request::Spawn { count: 1 })
+        ));
+        assert!(matches!(
+            request(r#"{"command": "target", "source": "roam"}"#),
+            Ok(Request::Target {
+                source: TargetSource::Roam
+            })
+        ));
+        assert!(matches!(
+            request(r#"{"command": "target", "source": {"point": [1, 2]}}"#),
+            Ok(Request::Target {
+                source: TargetSource::Point((1, 2))
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        for json in [
+            "",
+            "{}",
+            r#"{"command": "dance"}"#,
+            r#"{"command": "summon", "x": 10}"#,
+            r#"{"command": "summon", "x": "ten", "y": 5}"#,
+            r#"{"command": "summon", "x": 1, "y": 2, "z": 3}"#,
+            r#"{"command": "sleep", "cat": -1}"#,
+            r#"{"command": "target", "source": "mouse"}"#,
+            r#"{"command": "target", "source": {"point": [1]}}"#,
+        ] {
+            assert!(request(json).is_err(), "{json:?} parsed");
+        }
+    }
+
+    #[test]
+    fn stamps_event_lines() {
+        let event = line(&event_line(&Event::State {
+            cat: 2,
+            from: None,
+            to: "sleeping",
+            position: (3, 4),
+        }));
+        assert_eq!(event["event"], "state");
+        assert_eq!(event["cat"], 2);
+        assert_eq!(event["from"], serde_json::Value::Null);
+        assert_eq!(event["to"], "sleeping");
+        assert_eq!(event["position"], serde_json::json!([3, 4]));
+        assert!(event["time"].as_u64().is_some_and(|time| time > 0));
+    }
+
+    // The bookkeeping is shared by the whole process, so everything touching it is one test.
+    #[test]
+    fn turns_requests_into_orders_and_events() {
+        let id = register();
+        report(CatState {
+            id,
+            state: "idle",
+            frame_count: 0,
+            position: (5, 6),
+            target: (0, 0),
+            asleep: false,
+            summoned: None,
+        });
+
+        let first = subscribe();
+        let snapshot: Vec<_> = first.try_iter().map(|event| line(&event)).collect();
+        let mine: Vec<_> = snapshot.iter().filter(|event| event["cat"] == id).collect();
+        assert_eq!(mine.len(), 1);
+        assert_eq!(mine[0]["event"], "state");
+        assert_eq!(mine[0]["to"], "idle");
+
+        // Another subscriber gets a snapshot of its own, without repeating it to the first.
+        let second = subscribe();
+        assert!(second.try_iter().any(|event| line(&event)["cat"] == id));
+        assert_eq!(first.try_iter().count(), 0);
+
+        let answer = handle(
+            request(&format!(
+                r#"{{"command": "summon", "x": 1, "y": 2, "cat": {id}}}"#
+            ))
+            .unwrap(),
+        );
+        assert_eq!(answer["ok"], true);
+        assert_eq!(orders(id), [Order::Summon((1, 2))]);
+        assert!(orders(id).is_empty());
+
+        let answer = handle(request(r#"{"command": "wake", "cat": 0}"#).unwrap());
+        assert_eq!(answer["ok"], false);
+        assert_eq!(answer["error"], "no cat with id 0");
+
+        unregister(id);
+        let despawn = line(&first.try_recv().unwrap());
+        assert_eq!(despawn["event"], "despawn");
+        assert_eq!(despawn["cat"], id);
+        assert_eq!(despawn["position"], serde_json::json!([5, 6]));
+    }
+}
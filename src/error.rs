@@ -0,0 +1,215 @@
+//! What can go wrong, and what happens when it does.
+//!
+//! Trouble a cat can live with is handled where it happens: a connection that is not up yet is
+//! retried, a frame that cannot be drawn is skipped and a theme that cannot be loaded leaves the
+//! built-in cat in place. Everything else ends up here as an [`Error`], which is reported with a
+//! hint on what to try and written to a crash log, as are panics.
+
+use std::{
+    backtrace::Backtrace,
+    env, error, fmt, fs, io,
+    panic::{self, PanicHookInfo},
+    path::PathBuf,
+    process, thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use winit::error::EventLoopError;
+
+pub type BoxError = Box<dyn error::Error + Send + Sync>;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// How many times to try reaching the display server before giving up.
+const CONNECT_ATTEMPTS: u32 = 5;
+/// How long to wait after the first failed attempt, doubling after each one.
+const CONNECT_BACKOFF: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+pub enum Error {
+    /// The display server could not be reached, even after retrying.
+    Connect {
+        server: &'static str,
+        source: BoxError,
+    },
+    /// The display server lacks something the backend cannot do without.
+    Unsupported {
+        server: &'static str,
+        missing: &'static str,
+    },
+    /// A request the backend cannot go on without failed.
+    Request {
+        context: &'static str,
+        source: BoxError,
+    },
+    NoMonitors,
+    EventLoop(EventLoopError),
+    Io(io::Error),
+    Other(Box<dyn error::Error>),
+}
+
+impl Error {
+    /// What the user could try about it.
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            Error::Connect { server: "X11", .. } => Some(
+                "Check that DISPLAY points at a running X server, or pick another backend with \
+                 --backend wayland or --backend terminal.",
+            ),
+            Error::Connect {
+                server: "Wayland", ..
+            } => Some(
+                "Check that WAYLAND_DISPLAY points at a running compositor, or pick another \
+                 backend with --backend x11 or --backend terminal.",
+            ),
+            Error::Unsupported { .. } => Some("Pick another backend with --backend."),
+            Error::NoMonitors => Some("Connect a monitor, or use --backend terminal."),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Connect { server, source } => {
+                write!(f, "Error connecting to {server}: {source}")
+            }
+            Error::Unsupported { server, missing } => {
+                write!(f, "{server} has no {missing}, which the cat needs")
+            }
+            Error::Request { context, source } => write!(f, "Error {context}: {source}"),
+            Error::NoMonitors => write!(f, "Could not find any monitors"),
+            Error::EventLoop(err) => write!(f, "Error running the event loop: {err}"),
+            Error::Io(err) => err.fmt(f),
+            Error::Other(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Connect { source, .. } | Error::Request { source, .. } => Some(source.as_ref()),
+            Error::EventLoop(err) => Some(err),
+            Error::Io(err) => Some(err),
+            Error::Other(err) => Some(err.as_ref()),
+            Error::Unsupported { .. } | Error::NoMonitors => None,
+        }
+    }
+}
+
+impl From<EventLoopError> for Error {
+    fn from(err: EventLoopError) -> Self {
+        Error::EventLoop(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<Box<dyn error::Error>> for Error {
+    fn from(err: Box<dyn error::Error>) -> Self {
+        Error::Other(err)
+    }
+}
+
+/// Adds what was being done to a failed request, in place of an `expect`.
+pub trait Context<T> {
+    /// `context` reads as what was being done, such as `"creating the window"`.
+    fn context(self, context: &'static str) -> Result<T>;
+}
+
+impl<T, E: Into<BoxError>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: &'static str) -> Result<T> {
+        self.map_err(|err| Error::Request {
+            context,
+            source: err.into(),
+        })
+    }
+}
+
+/// Connects to `server` with `connect`, trying again a few times with a growing delay so a cat
+/// started along with the session does not lose the race against the display server.
+pub fn connect<T, E: Into<BoxError>>(
+    server: &'static str,
+    mut connect: impl FnMut() -> std::result::Result<T, E>,
+) -> Result<T> {
+    let mut delay = CONNECT_BACKOFF;
+    for _ in 1..CONNECT_ATTEMPTS {
+        match connect() {
+            Ok(connection) => return Ok(connection),
+            Err(err) => {
+                let err: BoxError = err.into();
+                tracing::warn!("Error connecting to {server}, retrying in {delay:?}: {err}");
+                thread::sleep(delay);
+                delay *= 2;
+            }
+        }
+    }
+    connect().map_err(|err| Error::Connect {
+        server,
+        source: err.into(),
+    })
+}
+
+/// `$XDG_STATE_HOME/oneko-rust/crash.log`, next to the saved state.
+fn crash_log_path() -> Option<PathBuf> {
+    Some(crate::persist::state_dir()?.join("crash.log"))
+}
+
+/// Writes what happened to the crash log, returning where it went.
+fn write_crash_log(what: &str) -> Option<PathBuf> {
+    let path = crash_log_path()?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let args = env::args().collect::<Vec<_>>().join(" ");
+    let text = format!(
+        "oneko-rust {} crashed at {time} (unix time)\ncommand line: {args}\n\n{what}\n",
+        env!("CARGO_PKG_VERSION"),
+    );
+    fs::create_dir_all(path.parent()?).ok()?;
+    fs::write(&path, text).ok()?;
+    Some(path)
+}
+
+/// Reports a fatal error with a hint on what to try, writes it to the crash log if asked to and
+/// exits.
+pub fn exit(err: &Error, crash_log: bool) -> ! {
+    eprintln!("{err}");
+    if let Some(hint) = err.hint() {
+        eprintln!("{hint}");
+    }
+    if !crash_log {
+        process::exit(1);
+    }
+
+    let mut what = format!("error: {err}");
+    // Each error already shows its direct source, so the chain starts one further down.
+    let mut source = error::Error::source(err).and_then(error::Error::source);
+    while let Some(err) = source {
+        what += &format!("\ncaused by: {err}");
+        source = err.source();
+    }
+    if let Some(path) = write_crash_log(&what) {
+        eprintln!("Details are in {}.", path.display());
+    }
+    process::exit(1);
+}
+
+/// Has panics write a crash log with a backtrace on top of the usual message.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info: &PanicHookInfo| {
+        default_hook(info);
+        let what = format!("panic: {info}\n\n{}", Backtrace::force_capture());
+        if let Some(path) = write_crash_log(&what) {
+            eprintln!("Details are in {}.", path.display());
+        }
+    }));
+}
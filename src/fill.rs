@@ -11,10 +11,13 @@ use std::mem::ManuallyDrop;
 use std::num::NonZeroU32;
 #[allow(unused_imports)]
 use std::{cell::RefCell, rc::Rc};
-use std::{cmp::min, collections::HashMap};
+use std::{
+    cmp::min,
+    collections::{hash_map::Entry, HashMap},
+};
 
 use image::{GenericImageView, RgbaImage, SubImage};
-use softbuffer::{Context, Surface};
+use softbuffer::{Context, SoftBufferError, Surface};
 use winit::window::{Window, WindowId};
 
 thread_local! {
@@ -36,18 +39,23 @@ pub struct GraphicsContext {
 }
 
 impl GraphicsContext {
-    fn new(w: Rc<Window>) -> Self {
-        Self {
-            context: RefCell::new(Context::new(w).expect("Failed to create a softbuffer context")),
+    fn new(w: Rc<Window>) -> Result<Self, SoftBufferError> {
+        Ok(Self {
+            context: RefCell::new(Context::new(w)?),
             surfaces: HashMap::new(),
-        }
+        })
     }
 
-    fn create_surface(&mut self, window: &Rc<Window>) -> &mut Surface<Rc<Window>, Rc<Window>> {
-        self.surfaces.entry(window.id()).or_insert_with(|| {
-            Surface::new(&self.context.borrow(), window.clone())
-                .expect("Failed to create a softbuffer surface")
-        })
+    fn create_surface(
+        &mut self,
+        window: &Rc<Window>,
+    ) -> Result<&mut Surface<Rc<Window>, Rc<Window>>, SoftBufferError> {
+        match self.surfaces.entry(window.id()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                Ok(entry.insert(Surface::new(&self.context.borrow(), window.clone())?))
+            }
+        }
     }
 
     fn destroy_surface(&mut self, window: &Window) {
@@ -55,30 +63,36 @@ impl GraphicsContext {
     }
 }
 
+/// Draws a frame into the window. A frame that cannot be drawn is skipped, and the next one tries
+/// again.
 pub fn fill_window(window: &Rc<Window>, data: SubImage<&RgbaImage>) {
+    if let Err(err) = try_fill_window(window, data) {
+        tracing::warn!("Skipping a frame: {err}");
+    }
+}
+
+fn try_fill_window(window: &Rc<Window>, data: SubImage<&RgbaImage>) -> Result<(), SoftBufferError> {
     GC.with(|gc| {
         let size = window.inner_size();
         let (Some(width), Some(height)) =
             (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
         else {
-            return;
+            return Ok(());
         };
 
         // Either get the last context used or create a new one.
         let mut gc = gc.borrow_mut();
-        let surface = gc
-            .get_or_insert_with(|| GraphicsContext::new(window.clone()))
-            .create_surface(window);
+        let gc = match &mut *gc {
+            Some(gc) => gc,
+            None => gc.insert(GraphicsContext::new(window.clone())?),
+        };
+        let surface = gc.create_surface(window)?;
 
         const TRANSPARENCY: u32 = 0x00000000;
 
-        surface
-            .resize(width, height)
-            .expect("Failed to resize the softbuffer surface");
+        surface.resize(width, height)?;
 
-        let mut buffer = surface
-            .buffer_mut()
-            .expect("Failed to get the softbuffer buffer");
+        let mut buffer = surface.buffer_mut()?;
 
         buffer.fill(TRANSPARENCY);
         for y in 0..min(size.height, data.height()) {
@@ -90,13 +104,10 @@ pub fn fill_window(window: &Rc<Window>, data: SubImage<&RgbaImage>) {
             }
         }
 
-        buffer
-            .present()
-            .expect("Failed to present the softbuffer buffer");
+        buffer.present()
     })
 }
 
-#[allow(dead_code)]
 pub fn cleanup_window(window: &Window) {
     GC.with(|gc| {
         let mut gc = gc.borrow_mut();
@@ -0,0 +1,107 @@
+//! Where log messages go.
+//!
+//! Debug builds log to stderr, release builds only when asked to with `--log`:
+//!
+//! - `stderr`: the terminal the cat was started from.
+//! - `file`: `$XDG_STATE_HOME/oneko-rust/logs/oneko-rust.<date>.log`, a new file every day and
+//!   the last week of them kept.
+//! - `journald`: the systemd journal, under the `oneko-rust` identifier.
+//!
+//! `RUST_LOG` picks what is logged, such as `RUST_LOG=oneko_rust=trace` for every X11 request.
+//! Without it warnings are logged, or everything from info up once `--log` is given, and
+//! `--debug` adds every step of every cat.
+
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    filter::{EnvFilter, LevelFilter},
+    prelude::*,
+};
+
+/// How many daily log files to keep.
+const KEPT_LOG_FILES: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogTarget {
+    Stderr,
+    File,
+    #[cfg(target_os = "linux")]
+    Journald,
+}
+
+impl LogTarget {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "stderr" => Some(LogTarget::Stderr),
+            "file" => Some(LogTarget::File),
+            #[cfg(target_os = "linux")]
+            "journald" => Some(LogTarget::Journald),
+            _ => None,
+        }
+    }
+}
+
+/// Sends log messages to `target`, or to stderr in debug builds when no target was given.
+pub fn init(target: Option<LogTarget>, debug: bool) {
+    let default_level = if target.is_some() || cfg!(debug_assertions) {
+        LevelFilter::INFO
+    } else {
+        LevelFilter::WARN
+    };
+    let mut filter = EnvFilter::builder()
+        .with_default_directive(default_level.into())
+        .from_env_lossy();
+    if debug {
+        filter = filter.add_directive(
+            "oneko_rust::oneko=debug"
+                .parse()
+                .expect("Error parsing log directive"),
+        );
+    }
+
+    let registry = tracing_subscriber::registry().with(filter);
+    match target.unwrap_or(LogTarget::Stderr) {
+        LogTarget::Stderr => registry
+            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+            .init(),
+        LogTarget::File => match log_file() {
+            Ok(appender) => registry
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_ansi(false)
+                        .with_writer(appender),
+                )
+                .init(),
+            Err(err) => {
+                registry
+                    .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+                    .init();
+                tracing::warn!("Error opening the log file, logging to stderr instead: {err}");
+            }
+        },
+        #[cfg(target_os = "linux")]
+        LogTarget::Journald => match tracing_journald::layer() {
+            Ok(layer) => registry
+                .with(layer.with_syslog_identifier("oneko-rust".into()))
+                .init(),
+            Err(err) => {
+                registry
+                    .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+                    .init();
+                tracing::warn!("Error connecting to journald, logging to stderr instead: {err}");
+            }
+        },
+    }
+}
+
+/// Today's log file, rolled over at midnight.
+fn log_file() -> Result<RollingFileAppender, Box<dyn std::error::Error>> {
+    let dir = crate::persist::state_dir()
+        .ok_or("Neither XDG_STATE_HOME nor HOME is set")?
+        .join("logs");
+    Ok(RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("oneko-rust")
+        .filename_suffix("log")
+        .max_log_files(KEPT_LOG_FILES)
+        .build(dir)?)
+}
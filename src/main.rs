@@ -1,9 +1,11 @@
 #![windows_subsystem = "windows"]
 
-use std::time::Instant;
+use std::process;
+use std::time::{Duration, Instant};
 
+use cli::{Args, Backend, Command};
+use config::Config;
 use oneko_window::OnekoWindow;
-use tracing_subscriber::filter::{EnvFilter, LevelFilter};
 
 use winit::application::ApplicationHandler;
 use winit::event::{StartCause, WindowEvent};
@@ -17,71 +19,291 @@ mod native_utils;
 #[path = "native_utils_windows.rs"]
 mod native_utils;
 
+mod accessory;
+mod aseprite;
+mod cli;
+mod config;
+mod control;
+mod error;
 mod fill;
+mod font;
+mod logging;
 mod oneko;
 mod oneko_window;
+mod persist;
+mod recolor;
+mod record;
+#[cfg(target_os = "linux")]
+mod reload;
+#[cfg(target_os = "linux")]
+mod screensaver;
+mod sheet_tool;
+mod shutdown;
+mod spawn;
 mod sprite_sheet;
+mod target;
+mod terminal;
+mod trail;
+mod workspace;
+mod xbm;
+#[cfg(target_os = "linux")]
+mod wayland_backend;
+#[cfg(target_os = "linux")]
+mod x11_backend;
+
+fn main() {
+    error::install_panic_hook();
+    let args = Args::parse();
+    // The other commands fail with a message that says it all, so only the cat leaves a crash log.
+    let crash_log = matches!(args.command, Command::Run);
+    if let Err(err) = run(args) {
+        error::exit(&err, crash_log);
+    }
+}
+
+fn run(args: Args) -> error::Result<()> {
+    // Log lines on stderr would land in the middle of the terminal backend's drawing.
+    let drawing_on_terminal = matches!(args.command, Command::Run)
+        && args.backend == Backend::Terminal
+        && args.log.is_none();
+    if !drawing_on_terminal {
+        logging::init(args.log, args.debug);
+    }
+
+    let config_path = args.config.clone().or_else(Config::default_path);
+    #[cfg(target_os = "linux")]
+    if matches!(args.command, Command::Run)
+        && args.screensaver.is_none()
+        && !args.new_instance
+        && forward_launch(&args, config_path.as_deref())
+    {
+        return Ok(());
+    }
+    #[cfg(target_os = "linux")]
+    let full_screen = args.screensaver.is_some() || args.backend == Backend::Wayland;
+    #[cfg(not(target_os = "linux"))]
+    let full_screen = false;
+    trail::set_drawn(!matches!(args.command, Command::Run) || full_screen);
+    config::set_source(config_path.as_deref(), &args.overrides);
+    if matches!(args.command, Command::Run | Command::Record(_))
+        && !config::apply(config_path.as_deref(), &args.overrides)
+    {
+        tracing::warn!("Carrying on with the built-in cat");
+    }
+
+    match args.command {
+        Command::Run => {
+            #[cfg(target_os = "linux")]
+            let remember = args.screensaver.is_none();
+            #[cfg(not(target_os = "linux"))]
+            let remember = true;
+            if remember {
+                persist::start();
+            }
+            accessory::watch_dates();
+            #[cfg(target_os = "linux")]
+            reload::watch();
+            #[cfg(target_os = "linux")]
+            control::serve();
+        }
+        Command::Record(options) => {
+            record::run(options)?;
+            return Ok(());
+        }
+        Command::Sheet(options) => {
+            if !sheet_tool::run(options)? {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        #[cfg(target_os = "linux")]
+        Command::Ctl(request) => {
+            if request["command"] == "subscribe" {
+                control::follow_events()?;
+                return Ok(());
+            }
+            let response = control::send(&request)?;
+            println!("{response}");
+            if response["ok"] != true {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(window) = args.screensaver {
+        shutdown::listen(|| ());
+        let result = screensaver::run(window, args.cats.unwrap_or(screensaver::HERD_SIZE));
+        control::close();
+        return result;
+    }
+
+    #[cfg(target_os = "linux")]
+    if args.backend != Backend::Winit {
+        shutdown::listen(|| ());
+    }
+
+    let result = match args.backend {
+        Backend::Winit => {
+            let event_loop = EventLoop::with_user_event().build()?;
+            #[cfg(target_os = "linux")]
+            {
+                let proxy = event_loop.create_proxy();
+                shutdown::listen(move || {
+                    let _ = proxy.send_event(Shutdown);
+                });
+            }
 
-fn main() -> Result<(), impl std::error::Error> {
-    #[cfg(debug_assertions)]
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::builder()
-                .with_default_directive(LevelFilter::INFO.into())
-                .from_env_lossy(),
-        )
-        .init();
+            let mut app = ControlFlowDemo::new();
+            event_loop.run_app(&mut app)?;
+            app.error.map_or(Ok(()), Err)
+        }
+        #[cfg(target_os = "linux")]
+        Backend::X11 => x11_backend::run(),
+        #[cfg(target_os = "linux")]
+        Backend::Wayland => wayland_backend::run(),
+        Backend::Terminal => terminal::run().map_err(error::Error::from),
+    };
+    #[cfg(target_os = "linux")]
+    control::close();
+    persist::save();
 
-    let event_loop = EventLoop::new().unwrap();
+    result
+}
 
-    let mut app = ControlFlowDemo::new();
-    event_loop.run_app(&mut app)
+/// Hands the arguments to an instance that is already running, returning whether there was one.
+/// A bare launch asks it for another cat, and theme options make it switch themes.
+#[cfg(target_os = "linux")]
+fn forward_launch(args: &Args, config_path: Option<&std::path::Path>) -> bool {
+    let theme = args.config.is_some() || args.overrides != config::Overrides::default();
+    let config = config_path
+        .filter(|_| theme)
+        .map(|path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+    let request = serde_json::json!({
+        "command": "launch",
+        "cats": args.cats.unwrap_or(if theme { 0 } else { 1 }),
+        "config": config,
+        "overrides": theme.then_some(&args.overrides),
+    });
+    let Ok(response) = control::send(&request) else {
+        return false;
+    };
+    if response["ok"] != true {
+        eprintln!(
+            "oneko-rust is already running and turned down the arguments: {}\n\
+             Pass --new-instance to start another one anyway.",
+            response["error"].as_str().unwrap_or_default()
+        );
+        process::exit(1);
+    }
+    true
 }
 
+/// How often to look for spawn requests while there are no cats to wake the event loop.
+const CONTROL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Sent to the event loop when a signal asks the program to quit.
+struct Shutdown;
+
 struct ControlFlowDemo {
-    oneko_window: Option<OnekoWindow>,
+    oneko_windows: Vec<OnekoWindow>,
+    /// Whether the cats are on their way out.
+    leaving: bool,
     next_update: Option<Instant>,
     wait_cancelled: bool,
     close_requested: bool,
+    /// What stopped the event loop early, if anything did.
+    error: Option<error::Error>,
+    /// The desktops that have had a cat of their own, with a cat per desktop.
+    #[cfg(target_os = "linux")]
+    desktops: std::collections::HashSet<u32>,
 }
 
 impl ControlFlowDemo {
     fn new() -> Self {
         Self {
-            oneko_window: None,
+            oneko_windows: Vec::new(),
+            leaving: false,
             next_update: None,
             wait_cancelled: false,
             close_requested: false,
+            error: None,
+            #[cfg(target_os = "linux")]
+            desktops: std::collections::HashSet::new(),
         }
     }
+
+    /// With a cat per desktop, whether the desktop being shown has yet to get one.
+    #[cfg(target_os = "linux")]
+    fn new_desktop(&mut self) -> bool {
+        if self.leaving || oneko::workspaces() != workspace::Workspaces::PerWorkspace {
+            return false;
+        }
+        let Some(desktop) = workspace::current_desktop() else {
+            return false;
+        };
+        let has_cat = self
+            .oneko_windows
+            .iter()
+            .any(|oneko_window| oneko_window.desktop() == Some(desktop));
+        // A desktop whose cat was taken away over the control socket stays without one.
+        self.desktops.insert(desktop) && !has_cat
+    }
 }
 
-impl ApplicationHandler for ControlFlowDemo {
+impl ApplicationHandler<Shutdown> for ControlFlowDemo {
     fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
-        // info!("new_events: {cause:?}");
+        tracing::trace!(?cause, "new events");
 
         match cause {
             StartCause::ResumeTimeReached { .. } | StartCause::Init => {
-                if let Some(oneko_window) = &mut self.oneko_window {
-                    oneko_window.update();
-                    self.next_update = Some(oneko_window.next_update());
-                } else {
-                    self.next_update = None;
+                let now = Instant::now();
+                for oneko_window in &mut self.oneko_windows {
+                    if oneko_window.next_update() <= now {
+                        oneko_window.update();
+                    }
                 }
+                self.next_update = self
+                    .oneko_windows
+                    .iter()
+                    .map(OnekoWindow::next_update)
+                    .min();
             }
             _ => {}
         }
 
-        self.wait_cancelled = match cause {
-            StartCause::WaitCancelled { .. } => true,
-            _ => false,
-        }
+        self.wait_cancelled = matches!(cause, StartCause::WaitCancelled { .. })
     }
 
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let oneko_window = OnekoWindow::new(event_loop);
+        if !self.oneko_windows.is_empty() {
+            return;
+        }
+        let oneko_window = match OnekoWindow::new(event_loop) {
+            Ok(oneko_window) => oneko_window,
+            Err(err) => {
+                self.error = Some(err);
+                event_loop.exit();
+                return;
+            }
+        };
         self.next_update = Some(oneko_window.next_update());
-        self.oneko_window = Some(oneko_window);
+        self.oneko_windows.push(oneko_window);
+        control::allow_herd();
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, _event: Shutdown) {
+        if shutdown::forced() {
+            event_loop.exit();
+            return;
+        }
+        self.leaving = true;
+        for oneko_window in &mut self.oneko_windows {
+            oneko_window.leave();
+        }
+        self.next_update = Some(Instant::now());
+        self.wait_cancelled = false;
     }
 
     fn window_event(
@@ -90,22 +312,54 @@ impl ApplicationHandler for ControlFlowDemo {
         window_id: WindowId,
         event: WindowEvent,
     ) {
-        if let Some(oneko_window) = &mut self.oneko_window {
-            if window_id == oneko_window.window_id() {
-                oneko_window.handle_window_event(&event);
-            }
+        if let Some(oneko_window) = self
+            .oneko_windows
+            .iter_mut()
+            .find(|oneko_window| oneko_window.window_id() == window_id)
+        {
+            oneko_window.handle_window_event(&event);
         }
-        // info!("{event:?}");
+        tracing::trace!(?window_id, ?event, "window event");
 
-        match event {
-            WindowEvent::CloseRequested => {
-                self.close_requested = true;
-            }
-            _ => (),
+        if event == WindowEvent::CloseRequested {
+            self.close_requested = true;
         }
     }
 
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let spawns = if self.leaving {
+            0
+        } else {
+            control::take_spawns()
+        };
+        #[cfg(target_os = "linux")]
+        let spawns = spawns + usize::from(self.new_desktop());
+        for _ in 0..spawns {
+            // The cats already out keep going if another one cannot join them.
+            match OnekoWindow::new(event_loop) {
+                Ok(oneko_window) => self.oneko_windows.push(oneko_window),
+                Err(err) => tracing::error!("Could not add a cat: {err}"),
+            }
+        }
+        let count = self.oneko_windows.len();
+        self.oneko_windows
+            .retain(|oneko_window| !oneko_window.removed() && !oneko_window.gone());
+        if self.leaving && self.oneko_windows.is_empty() {
+            event_loop.exit();
+            return;
+        }
+        if spawns > 0 || self.oneko_windows.len() != count {
+            self.next_update = self
+                .oneko_windows
+                .iter()
+                .map(OnekoWindow::next_update)
+                .min();
+            self.wait_cancelled = false;
+        }
+        if self.next_update.is_none() {
+            self.next_update = Some(Instant::now() + CONTROL_POLL_INTERVAL);
+        }
+
         if !self.wait_cancelled {
             if let Some(next_update) = self.next_update {
                 event_loop.set_control_flow(ControlFlow::WaitUntil(next_update));
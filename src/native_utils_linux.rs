@@ -1,6 +1,5 @@
 use std::cell::RefCell;
 
-use tracing::info;
 use winit::{
     dpi::PhysicalPosition,
     raw_window_handle::{HandleError, HasDisplayHandle, RawDisplayHandle},
@@ -12,18 +11,19 @@ use x11rb::{
 };
 
 thread_local! {
-  static XCB_CONNECTION: RefCell<Option<x11rb::xcb_ffi::XCBConnection>> = RefCell::new(None);
+  static XCB_CONNECTION: RefCell<Option<x11rb::xcb_ffi::XCBConnection>> = const { RefCell::new(None) };
 }
 
-pub fn with_x11_connection<R>(body: impl FnOnce(&mut XCBConnection) -> R) -> R {
+/// Runs `body` with this thread's X connection, connecting first if need be. Returns `None` if
+/// there is no X server to connect to, in which case the next call tries again.
+pub fn with_x11_connection<R>(body: impl FnOnce(&mut XCBConnection) -> R) -> Option<R> {
     XCB_CONNECTION.with(|maybe_connection| {
         let mut binding = maybe_connection.borrow_mut();
-        let connection = binding.get_or_insert_with(|| {
-            XCBConnection::connect(None)
-                .expect("Error connecting to X11")
-                .0
-        });
-        body(connection)
+        let connection = match &mut *binding {
+            Some(connection) => connection,
+            None => binding.insert(XCBConnection::connect(None).ok()?.0),
+        };
+        Some(body(connection))
     })
 }
 
@@ -34,7 +34,7 @@ pub fn get_cursor_position(
 
     match display_handle {
         RawDisplayHandle::Xlib(xlib_display) => {
-            xlib_display.display.expect("No xlib display");
+            xlib_display.display.ok_or(HandleError::Unavailable)?;
 
             with_x11_connection(|conn| -> Result<PhysicalPosition<i32>, HandleError> {
                 let setup = conn.setup();
@@ -56,7 +56,7 @@ pub fn get_cursor_position(
                 }
 
                 let Some(pointer_query) = found_pointer else {
-                    panic!("Pointer could not be found");
+                    return Err(HandleError::Unavailable);
                 };
 
                 Ok(PhysicalPosition::new(
@@ -65,6 +65,7 @@ pub fn get_cursor_position(
                 ))
                 // Ok(PhysicalPosition::new(pointer_query.win_x, pointer_query.win_y))
             })
+            .unwrap_or(Err(HandleError::Unavailable))
         }
         // _ => Err(HandleError::NotSupported),
         _ => Ok(PhysicalPosition::new(400, 400)),
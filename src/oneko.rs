@@ -1,61 +1,399 @@
 use std::{
-    sync::OnceLock,
-    time::{self, Duration},
+    sync::{Arc, Mutex, RwLock},
+    time,
 };
 
-use image::{imageops::FilterType, RgbaImage, SubImage};
+use image::{GenericImageView, RgbaImage, SubImage};
 use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+use crate::control::{self, CatState, Event, Order, TargetSource};
+use crate::persist::{self, SavedCat};
+use crate::recolor::{self, Color};
+use crate::spawn::SpawnConfig;
+use crate::sprite_sheet::{Animation, Manifest, SpriteSheet};
+use crate::target::Roam;
+use crate::trail::TrailConfig;
+use crate::workspace::Workspaces;
+
+/// How much the sheet is blown up on a monitor with a scale factor of 1.
+pub const DEFAULT_SCALE: f64 = 2.0;
+/// Pixels the cat runs per step, before scaling.
+pub const SPEED: f64 = 10.0;
+/// How close the cat gets to its target before it stops running, before scaling.
+pub const FOLLOW_DISTANCE: f64 = 60.0;
+/// How long curling up and fading away takes.
+const FADE_TIME: time::Duration = time::Duration::from_millis(1500);
+/// The longest an exit animation may hold up quitting.
+const EXIT_TIMEOUT: time::Duration = time::Duration::from_secs(3);
+/// How often a paused cat checks whether it may move again.
+const PAUSED_INTERVAL: time::Duration = time::Duration::from_millis(100);
 
-use crate::sprite_sheet::{Animation, FrameRef, SpriteSheet};
+const ONEKO_IMG_DATA: &[u8] = include_bytes!("./maia_oneko.gif");
+const ONEKO_MANIFEST: &str = include_str!("./maia_oneko.toml");
+static THEME: RwLock<Option<Theme>> = RwLock::new(None);
 
-const SCALE: u32 = 2;
-const SPEED: f64 = 10.0 * (SCALE as f64);
-const FOLLOW_DISTANCE: f64 = 60.0 * (SCALE as f64);
+/// The sprite sheet embedded in the binary, at its original size.
+pub fn builtin_sprite_sheet() -> SpriteSheet {
+    let image = image::load_from_memory(ONEKO_IMG_DATA).expect("Error loading spritesheet image");
+    let manifest = Manifest::parse(ONEKO_MANIFEST).expect("Error parsing sprite manifest");
 
-const ONEKO_IMG_DATA: &[u8] = include_bytes!("./maia_oneko.gif");
-static SPRITE_SHEET: OnceLock<SpriteSheet> = OnceLock::new();
+    SpriteSheet::from_manifest(&manifest, image)
+}
+
+/// What the cat does when the program is asked to quit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitAnimation {
+    /// Run off the nearest edge of the monitor.
+    #[default]
+    RunOff,
+    /// Curl up to sleep and fade away.
+    CurlUp,
+    /// Vanish at once.
+    None,
+}
+
+type ScaledSheets = Vec<((f64, Option<Color>), Arc<SpriteSheet>)>;
+
+/// Everything about a cat that may change while it runs: how it looks and how it moves.
+#[derive(Clone)]
+pub struct Theme {
+    /// The sheet at its original size.
+    sheet: Arc<SpriteSheet>,
+    speed: f64,
+    follow_distance: f64,
+    /// A fixed scale overriding the one derived from the monitor.
+    scale: Option<f64>,
+    /// Colors handed out to cats in turn so several of them can be told apart.
+    tints: Vec<Color>,
+    /// Paw prints to leave behind, if any.
+    trail: Option<TrailConfig>,
+    exit_animation: ExitAnimation,
+    spawn: SpawnConfig,
+    workspaces: Workspaces,
+    /// Scaled and tinted copies of `sheet`, shared by every cat using this theme.
+    scaled: Arc<Mutex<ScaledSheets>>,
+}
+
+impl Theme {
+    /// A theme drawing `sheet`, with everything else as the built-in cat has it until changed
+    /// with the `with_` methods.
+    pub fn new(sheet: SpriteSheet) -> Self {
+        Self {
+            sheet: Arc::new(sheet),
+            speed: SPEED,
+            follow_distance: FOLLOW_DISTANCE,
+            scale: None,
+            tints: Vec::new(),
+            trail: None,
+            exit_animation: ExitAnimation::default(),
+            spawn: SpawnConfig::default(),
+            workspaces: Workspaces::default(),
+            scaled: Arc::default(),
+        }
+    }
+
+    /// Pixels per step, before scaling.
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// How far away the target may be before the cat gets up, in pixels before scaling.
+    pub fn with_follow_distance(mut self, follow_distance: f64) -> Self {
+        self.follow_distance = follow_distance;
+        self
+    }
+
+    /// A fixed scale, or `None` to follow the monitor's scale factor.
+    pub fn with_scale(mut self, scale: Option<f64>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Colors handed out to cats in turn.
+    pub fn with_tints(mut self, tints: Vec<Color>) -> Self {
+        self.tints = tints;
+        self
+    }
+
+    /// Paw prints to leave behind, if any.
+    pub fn with_trail(mut self, trail: Option<TrailConfig>) -> Self {
+        self.trail = trail;
+        self
+    }
+
+    /// What the cats do when asked to quit.
+    pub fn with_exit_animation(mut self, exit_animation: ExitAnimation) -> Self {
+        self.exit_animation = exit_animation;
+        self
+    }
+
+    /// Where new cats show up.
+    pub fn with_spawn(mut self, spawn: SpawnConfig) -> Self {
+        self.spawn = spawn;
+        self
+    }
+
+    /// Which virtual desktops the cats show up on.
+    pub fn with_workspaces(mut self, workspaces: Workspaces) -> Self {
+        self.workspaces = workspaces;
+        self
+    }
+
+    fn tint(&self, index: usize) -> Option<Color> {
+        (!self.tints.is_empty()).then(|| self.tints[index % self.tints.len()])
+    }
+
+    /// The scale to draw at on a monitor with the given scale factor.
+    fn scale(&self, scale_factor: f64) -> f64 {
+        self.scale.unwrap_or(DEFAULT_SCALE * scale_factor)
+    }
+
+    fn sheet_at(&self, scale: f64, tint: Option<Color>) -> Arc<SpriteSheet> {
+        let key = (scale, tint);
+        let mut scaled = self.scaled.lock().unwrap();
+        if let Some((_, sheet)) = scaled.iter().find(|(sheet_key, _)| *sheet_key == key) {
+            return sheet.clone();
+        }
+
+        let mut sheet = self.sheet.scaled(scale);
+        if let Some(tint) = tint {
+            recolor::tint(sheet.image_mut(), tint);
+        }
+        let sheet = Arc::new(sheet);
+        scaled.push((key, sheet.clone()));
+        sheet
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new(builtin_sprite_sheet())
+    }
+}
+
+/// Replaces the theme of every cat. Each one picks it up on its next step.
+pub fn set_theme(theme: Theme) {
+    *THEME.write().unwrap() = Some(theme);
+}
+
+/// How the current theme has cats handle virtual desktops.
+pub fn workspaces() -> Workspaces {
+    THEME
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(|theme| theme.workspaces)
+        .unwrap_or_default()
+}
+
+impl Theme {
+    /// Paw prints to leave behind, if any.
+    pub fn trail(&self) -> Option<TrailConfig> {
+        self.trail
+    }
+}
+
+fn current_theme() -> Theme {
+    if let Some(theme) = &*THEME.read().unwrap() {
+        return theme.clone();
+    }
+    THEME
+        .write()
+        .unwrap()
+        .get_or_insert_with(Theme::default)
+        .clone()
+}
+
+/// What the cat made of its last step, for debugging the chase.
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    /// The animation it is playing.
+    pub state: &'static str,
+    pub frame_count: u32,
+    /// How far away the target may be before the cat gets up, in pixels at the current scale.
+    pub follow_distance: f64,
+    /// Where the cat is heading: the mouse plus this cat's offset.
+    pub target: (i32, i32),
+    /// From the cat's center to the target.
+    pub distance: f64,
+    /// The wall the cat is up against, if it is the one facing the target.
+    pub wall: Option<&'static str>,
+    pub position: (i32, i32),
+    pub size: (i32, i32),
+    /// Which way the cat is running, if it is.
+    pub heading: Option<(i32, i32)>,
+    pub scale: f64,
+}
 
 pub struct Oneko {
+    theme: Theme,
+    scale_factor: f64,
+    scale: f64,
+    /// Which of the theme's tints this cat wears.
+    tint: usize,
+    /// The theme's sheet at `scale`, tinted.
+    sheet: Arc<SpriteSheet>,
     anim: AnimState,
     frame_count: u32,
     offset: (i32, i32),
+    last_step: Option<Step>,
+    /// Tells this cat apart in the control socket.
+    id: u64,
+    /// Put to sleep over the control socket, and staying asleep until woken.
+    asleep: bool,
+    /// A point to go to instead of the usual target, from the control socket.
+    summoned: Option<(i32, i32)>,
+    removed: bool,
+    /// For the `roam` target source.
+    roam: Option<Roam>,
+    /// The name of the monitor the cat is on, if the backend knows it.
+    monitor: Option<String>,
+    /// Whether this is the cat remembered across restarts.
+    persistent: bool,
+    /// The direction the cat is running in from off screen, until it is all the way in.
+    entering: Option<AnimStateMoving>,
+    leaving: Option<Leaving>,
+    /// The current frame with its alpha turned down, while fading away.
+    faded: Option<RgbaImage>,
 }
 
-impl Default for Oneko {
-    fn default() -> Self {
-        SPRITE_SHEET.get_or_init(|| {
-            let image =
-                image::load_from_memory(ONEKO_IMG_DATA).expect("Error loading spritesheet image");
-
-            let image = image.resize(
-                image.width() * SCALE,
-                image.height() * SCALE,
-                FilterType::Nearest,
-            );
+/// How far along the exit animation is.
+#[derive(Debug, Clone, Copy)]
+struct Leaving {
+    since: time::Instant,
+    done: bool,
+}
 
-            SpriteSheet::new(image, (32 * SCALE, 32 * SCALE))
-        });
+impl Drop for Oneko {
+    fn drop(&mut self) {
+        control::unregister(self.id);
+    }
+}
 
+impl Default for Oneko {
+    fn default() -> Self {
         // let mut rng = rand::thread_rng();
         // let offset = (rng.gen_range(-50..=50), rng.gen_range(-50..=50));
 
+        let theme = current_theme();
+        let scale = theme.scale(1.0);
         Self {
+            sheet: theme.sheet_at(scale, theme.tint(0)),
+            theme,
+            scale_factor: 1.0,
+            scale,
+            tint: 0,
             anim: AnimState::Idle(AnimStateIdle::Idle),
             frame_count: 0,
             offset: (0, 0),
+            last_step: None,
+            id: control::register(),
+            asleep: false,
+            summoned: None,
+            removed: false,
+            roam: None,
+            monitor: None,
+            persistent: false,
+            entering: None,
+            leaving: None,
+            faded: None,
         }
     }
 }
 
 impl Oneko {
+    /// How much the sheet is blown up, as the theme asks or as suits the monitor.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Tells the cat the scale factor of the monitor it is on, 1 being roughly 96 DPI. The cat
+    /// is redrawn at the new size from its next frame on.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+        self.refresh();
+    }
+
+    /// Makes this the `index`th cat to be told apart by color. The theme's tints are handed out
+    /// in order, wrapping around when there are more cats than tints.
+    pub fn set_tint(&mut self, index: usize) {
+        self.tint = index;
+        self.refresh();
+    }
+
+    /// Tells the cat the name of the monitor it is on, to remember it by across restarts.
+    pub fn set_monitor(&mut self, name: Option<String>) {
+        if name != self.monitor {
+            tracing::info!(cat = self.id, from = ?self.monitor, to = ?name, "monitor changed");
+        }
+        self.monitor = name;
+    }
+
+    /// Makes this the cat remembered across restarts if no other cat is yet, and has it pick up
+    /// where the last run left off. Returns what was saved, for the backend to put the cat back
+    /// where it was.
+    pub fn restore(&mut self) -> Option<SavedCat> {
+        let saved = persist::claim()?;
+        self.persistent = true;
+        let saved = saved?;
+        if let Some(anim) = AnimState::from_name(&saved.state) {
+            self.anim = anim;
+        }
+        self.asleep = saved.asleep;
+        self.monitor = saved.monitor.clone();
+        tracing::info!(
+            cat = self.id,
+            state = saved.state,
+            monitor = ?saved.monitor,
+            position = ?saved.position,
+            "restored"
+        );
+        Some(saved)
+    }
+
+    /// Picks up the current theme and rescales the sheet if needed.
+    fn refresh(&mut self) {
+        self.theme = current_theme();
+        self.scale = self.theme.scale(self.scale_factor);
+        self.sheet = self.theme.sheet_at(self.scale, self.theme.tint(self.tint));
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(cat = self.id))]
     pub fn act(
         &mut self,
         (cat_x, cat_y): (i32, i32),
-        (mouse_x, mouse_y): (i32, i32),
+        mouse: (i32, i32),
         (monitor_width, monitor_height): (i32, i32),
     ) -> (time::Duration, (i32, i32)) {
+        self.refresh();
+        if self.leaving.is_some() {
+            return self.act_leaving((cat_x, cat_y), (monitor_width, monitor_height));
+        }
+
+        for order in control::orders(self.id) {
+            self.obey(order);
+        }
+        if control::paused() {
+            return (PAUSED_INTERVAL, (cat_x, cat_y));
+        }
+        if let Some(direction) = self.entering {
+            return self.act_entering(direction, (cat_x, cat_y), (monitor_width, monitor_height));
+        }
+        let monitor = (monitor_width, monitor_height);
+        let (mouse_x, mouse_y) = match (self.summoned, control::target_source()) {
+            (Some(point), _) | (None, TargetSource::Point(point)) => point,
+            (None, TargetSource::Pointer) => mouse,
+            (None, TargetSource::Roam) => self
+                .roam
+                .get_or_insert_with(|| Roam::new(monitor))
+                .target(monitor),
+        };
+
         let (offset_x, offset_y) = self.offset;
-        let (cat_width, cat_height) = SPRITE_SHEET.get().unwrap().get_size();
+        let (cat_width, cat_height) = self.get_frame().dimensions();
         let (cat_width, cat_height) = (cat_width as i32, cat_height as i32);
         let cat_cx = cat_x + cat_width / 2;
         let cat_cy = cat_y + cat_height / 2;
@@ -67,14 +405,14 @@ impl Oneko {
         let mouse_dyf: f64 = mouse_dy.into();
         let distance = f64::sqrt(mouse_dxf * mouse_dxf + mouse_dyf * mouse_dyf);
 
-        let (_, touching_wall, mut scratch_anim) = [
-            ((0, -1), AnimStateScratch::ScratchWallN),
-            ((1, 0), AnimStateScratch::ScratchWallE),
-            ((0, 1), AnimStateScratch::ScratchWallS),
-            ((-1, 0), AnimStateScratch::ScratchWallW),
+        let (_, touching_wall, mut scratch_anim, wall) = [
+            ((0, -1), AnimStateScratch::ScratchWallN, "north"),
+            ((1, 0), AnimStateScratch::ScratchWallE, "east"),
+            ((0, 1), AnimStateScratch::ScratchWallS, "south"),
+            ((-1, 0), AnimStateScratch::ScratchWallW, "west"),
         ]
         .into_iter()
-        .map(|((x, y), anim)| {
+        .map(|((x, y), anim, wall)| {
             let mouse_dist = mouse_dx * x + mouse_dy * y;
             let touching = match (x, y) {
                 (0, -1) => cat_y <= 0,
@@ -83,32 +421,29 @@ impl Oneko {
                 (-1, 0) => cat_x <= 0,
                 _ => unreachable!(),
             };
-            (mouse_dist, touching, anim)
+            (mouse_dist, touching, anim, wall)
         })
-        .max_by_key(|(mouse_dist, _, _)| *mouse_dist)
+        .max_by_key(|(mouse_dist, _, _, _)| *mouse_dist)
         .unwrap();
 
         if !touching_wall {
             scratch_anim = AnimStateScratch::ScratchSelf;
         }
 
-        let active = distance > FOLLOW_DISTANCE && !touching_wall;
+        let active = distance > self.theme.follow_distance * self.scale && !touching_wall;
 
         let next_moving_state: AnimState =
             AnimState::Moving(AnimStateMoving::from_vector((mouse_dx, mouse_dy)));
         let next_anim = match self.anim {
+            _ if self.asleep => AnimState::Idle(AnimStateIdle::Sleeping),
             AnimState::Moving(..) if !active => AnimState::Idle(AnimStateIdle::Idle),
             AnimState::Moving(..) => next_moving_state,
             AnimState::Idle(..) if active => AnimState::Alert,
             // Pick something to do
             AnimState::Idle(AnimStateIdle::Idle) if self.frame_count > 10 => AnimState::Idle(
-                [
-                    AnimStateIdle::Scratch(scratch_anim),
-                    AnimStateIdle::Tired,
-                ]
-                .choose(&mut rand::thread_rng())
-                .unwrap()
-                .clone(),
+                *[AnimStateIdle::Scratch(scratch_anim), AnimStateIdle::Tired]
+                    .choose(&mut rand::thread_rng())
+                    .unwrap(),
             ),
             // Done scratching
             AnimState::Idle(AnimStateIdle::Scratch(..)) if self.frame_count > 9 => {
@@ -125,17 +460,24 @@ impl Oneko {
         };
 
         if next_anim != self.anim {
+            persist::count(|stats| match next_anim {
+                AnimState::Idle(AnimStateIdle::Sleeping) => stats.naps += 1,
+                AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchSelf)) => (),
+                AnimState::Idle(AnimStateIdle::Scratch(..)) => stats.wall_scratches += 1,
+                _ => (),
+            });
             self.anim = next_anim;
             self.frame_count = 0;
         } else {
             self.frame_count += 1;
         }
 
-        let animation = get_animation(self.anim);
+        let interval = self.animation().duration(self.frame_count as usize);
+        let speed = self.theme.speed * self.scale;
         let (mut delta_x, mut delta_y) = match next_anim {
             AnimState::Moving(..) => (
-                (mouse_dxf / distance * SPEED) as i32,
-                (mouse_dyf / distance * SPEED) as i32,
+                (mouse_dxf / distance * speed) as i32,
+                (mouse_dyf / distance * speed) as i32,
             ),
             _ => (0, 0),
         };
@@ -146,23 +488,294 @@ impl Oneko {
             delta_y = mouse_dy;
         }
 
-        let cat_x = (cat_x + delta_x).clamp(0, monitor_width - cat_width);
-        let cat_y = (cat_y + delta_y).clamp(0, monitor_width - cat_width);
+        // A monitor smaller than the cat pins it to the top left instead of panicking.
+        let cat_x = (cat_x + delta_x).clamp(0, (monitor_width - cat_width).max(0));
+        let cat_y = (cat_y + delta_y).clamp(0, (monitor_height - cat_height).max(0));
+        if delta_x != 0 || delta_y != 0 {
+            persist::count(|stats| stats.distance += f64::hypot(delta_x.into(), delta_y.into()));
+        }
 
-        return (animation.interval, (cat_x, cat_y));
+        let step = Step {
+            state: self.anim.name(),
+            frame_count: self.frame_count,
+            follow_distance: self.theme.follow_distance * self.scale,
+            target: (mouse_x + offset_x, mouse_y + offset_y),
+            distance,
+            wall: touching_wall.then_some(wall),
+            position: (cat_x, cat_y),
+            size: (cat_width, cat_height),
+            heading: match next_anim {
+                AnimState::Moving(direction) => Some(direction.vector()),
+                _ => None,
+            },
+            scale: self.scale,
+        };
+        tracing::debug!(
+            state = step.state,
+            frame_count = step.frame_count,
+            follow_distance = step.follow_distance,
+            target = ?step.target,
+            distance = step.distance,
+            wall = step.wall,
+            position = ?step.position,
+            "step"
+        );
+        let last_step = self.last_step.replace(step);
+        control::report(CatState {
+            id: self.id,
+            state: step.state,
+            frame_count: step.frame_count,
+            position: step.position,
+            target: step.target,
+            asleep: self.asleep,
+            summoned: self.summoned,
+        });
+        if self.persistent {
+            persist::update(SavedCat {
+                monitor: self.monitor.clone(),
+                position: step.position,
+                state: step.state.into(),
+                asleep: self.asleep,
+            });
+        }
+        let from = last_step.map(|last_step| last_step.state);
+        if from != Some(step.state) {
+            tracing::info!(
+                from = from.unwrap_or("none"),
+                to = step.state,
+                position = ?step.position,
+                "state changed"
+            );
+            control::emit(Event::State {
+                cat: self.id,
+                from,
+                to: step.state,
+                position: step.position,
+            });
+        }
+        if let Some(wall) = step
+            .wall
+            .filter(|_| last_step.and_then(|last| last.wall) != step.wall)
+        {
+            tracing::debug!(wall, position = ?step.position, "hit a wall");
+            control::emit(Event::Wall {
+                cat: self.id,
+                wall,
+                position: step.position,
+            });
+        }
+
+        (interval, (cat_x, cat_y))
+    }
+
+    /// Has the cat run in along `heading` from off screen before it starts chasing anything.
+    pub fn enter(&mut self, heading: (i32, i32)) {
+        tracing::info!(cat = self.id, ?heading, "entering");
+        self.entering = Some(AnimStateMoving::from_vector(heading));
+    }
+
+    /// One step of running in from off screen, in place of the usual chase.
+    fn act_entering(
+        &mut self,
+        direction: AnimStateMoving,
+        (cat_x, cat_y): (i32, i32),
+        (monitor_width, monitor_height): (i32, i32),
+    ) -> (time::Duration, (i32, i32)) {
+        let next_anim = AnimState::Moving(direction);
+        if next_anim != self.anim {
+            self.anim = next_anim;
+            self.frame_count = 0;
+        } else {
+            self.frame_count += 1;
+        }
+
+        let (cat_width, cat_height) = self.get_frame().dimensions();
+        let (cat_width, cat_height) = (cat_width as i32, cat_height as i32);
+        let speed = ((self.theme.speed * self.scale) as i32).max(1);
+        let (dx, dy) = direction.vector();
+        let (mut x, mut y) = (cat_x + dx * speed, cat_y + dy * speed);
+        // Done once past the edge it came in over. A cat bigger than the monitor never fits
+        // inside it, so whether the rest of it does is left alone.
+        let crossed = |position: i32, step: i32, size: i32, room: i32| match step.signum() {
+            1 => position >= 0,
+            -1 => position + size <= room,
+            _ => true,
+        };
+        if crossed(x, dx, cat_width, monitor_width) && crossed(y, dy, cat_height, monitor_height) {
+            self.entering = None;
+            let clamp = |position: i32, size: i32, room: i32| {
+                position.clamp((room - size).min(0), (room - size).max(0))
+            };
+            x = clamp(x, cat_width, monitor_width);
+            y = clamp(y, cat_height, monitor_height);
+        }
+
+        (self.animation().duration(self.frame_count as usize), (x, y))
+    }
+
+    /// Whether the cat is still running in from off screen.
+    pub fn entering(&self) -> bool {
+        self.entering.is_some()
+    }
+
+    /// Starts the theme's exit animation. Once it is over the cat is [`gone`](Self::gone) and
+    /// the backend can take its window down.
+    pub fn leave(&mut self) {
+        if self.leaving.is_none() {
+            tracing::info!(cat = self.id, animation = ?self.theme.exit_animation, "leaving");
+            self.leaving = Some(Leaving {
+                since: time::Instant::now(),
+                done: self.theme.exit_animation == ExitAnimation::None,
+            });
+        }
+    }
+
+    /// Whether the exit animation has started.
+    pub fn leaving(&self) -> bool {
+        self.leaving.is_some()
+    }
+
+    /// Whether the cat has finished leaving.
+    pub fn gone(&self) -> bool {
+        self.leaving
+            .is_some_and(|leaving| leaving.done || leaving.since.elapsed() >= EXIT_TIMEOUT)
+    }
+
+    /// One step of the exit animation, in place of the usual chase.
+    fn act_leaving(
+        &mut self,
+        (cat_x, cat_y): (i32, i32),
+        (monitor_width, monitor_height): (i32, i32),
+    ) -> (time::Duration, (i32, i32)) {
+        let Some(leaving) = self.leaving else {
+            unreachable!("act_leaving is only called while leaving");
+        };
+        let (cat_width, cat_height) = self.get_frame().dimensions();
+        let (cat_width, cat_height) = (cat_width as i32, cat_height as i32);
+
+        let next_anim = match self.theme.exit_animation {
+            ExitAnimation::RunOff => {
+                // How far the cat has to run to be out of sight through each edge.
+                let (_, direction) = [
+                    (cat_y + cat_height, AnimStateMoving::N),
+                    (monitor_width - cat_x, AnimStateMoving::E),
+                    (monitor_height - cat_y, AnimStateMoving::S),
+                    (cat_x + cat_width, AnimStateMoving::W),
+                ]
+                .into_iter()
+                .min_by_key(|(distance, _)| *distance)
+                .unwrap();
+                AnimState::Moving(direction)
+            }
+            ExitAnimation::CurlUp => match self.anim {
+                AnimState::Idle(AnimStateIdle::Tired) if self.frame_count > 3 => {
+                    AnimState::Idle(AnimStateIdle::Sleeping)
+                }
+                AnimState::Idle(AnimStateIdle::Tired | AnimStateIdle::Sleeping) => self.anim,
+                _ => AnimState::Idle(AnimStateIdle::Tired),
+            },
+            ExitAnimation::None => self.anim,
+        };
+        if next_anim != self.anim {
+            self.anim = next_anim;
+            self.frame_count = 0;
+        } else {
+            self.frame_count += 1;
+        }
+
+        let (mut x, mut y) = (cat_x, cat_y);
+        let mut done = leaving.done;
+        self.faded = None;
+        match (self.theme.exit_animation, next_anim) {
+            (ExitAnimation::RunOff, AnimState::Moving(direction)) => {
+                let speed = (self.theme.speed * self.scale) as i32;
+                let (dx, dy) = direction.vector();
+                (x, y) = (x + dx * speed, y + dy * speed);
+                done = x + cat_width <= 0
+                    || x >= monitor_width
+                    || y + cat_height <= 0
+                    || y >= monitor_height;
+            }
+            (ExitAnimation::CurlUp, _) => {
+                let opacity = 1.0 - leaving.since.elapsed().as_secs_f64() / FADE_TIME.as_secs_f64();
+                done = opacity <= 0.0;
+                let mut faded = self.get_frame().to_image();
+                for pixel in faded.pixels_mut() {
+                    pixel[3] = (pixel[3] as f64 * opacity.max(0.0)) as u8;
+                }
+                self.faded = Some(faded);
+            }
+            _ => (),
+        }
+        self.leaving = Some(Leaving { done, ..leaving });
+
+        (self.animation().duration(self.frame_count as usize), (x, y))
+    }
+
+    fn obey(&mut self, order: Order) {
+        tracing::info!(?order, "obeying");
+        match order {
+            Order::Summon(point) => self.summoned = Some(point),
+            Order::Release => self.summoned = None,
+            Order::Sleep => self.asleep = true,
+            Order::Wake => {
+                self.asleep = false;
+                self.alert();
+            }
+            Order::Remove => self.removed = true,
+        }
+    }
+
+    /// Whether the cat has been asked to leave over the control socket. Backends showing several
+    /// cats drop it.
+    pub fn removed(&self) -> bool {
+        self.removed
+    }
+
+    /// Where the current theme has new cats show up.
+    pub fn spawn(&self) -> &SpawnConfig {
+        &self.theme.spawn
+    }
+
+    /// The paw prints the current theme asks for.
+    pub fn trail(&self) -> Option<TrailConfig> {
+        self.theme.trail
+    }
+
+    /// What the cat made of its last call to `act`.
+    pub fn last_step(&self) -> Option<Step> {
+        self.last_step
     }
 
     pub fn click(&mut self) {
+        tracing::info!(cat = self.id, "clicked");
+        if let Some(step) = self.last_step {
+            control::emit(Event::Click {
+                cat: self.id,
+                position: step.position,
+            });
+        }
+        persist::count(|stats| stats.clicks += 1);
+        self.alert();
+    }
+
+    fn alert(&mut self) {
         self.anim = AnimState::Alert;
         self.frame_count = 0;
     }
 
     pub fn get_frame(&self) -> SubImage<&RgbaImage> {
-        let animation = get_animation(self.anim);
-        return SPRITE_SHEET
-            .get()
-            .unwrap()
-            .get_anim_view(&animation, self.frame_count as usize);
+        if let Some(faded) = &self.faded {
+            return faded.view(0, 0, faded.width(), faded.height());
+        }
+        self.sheet
+            .get_anim_view(self.animation(), self.frame_count as usize)
+    }
+
+    fn animation(&self) -> &Animation {
+        self.sheet
+            .get_animation(self.anim.name())
+            .expect("Sprite sheet is missing an animation")
     }
 
     // pub fn get_icon(&self, size: u32) -> Icon {
@@ -187,6 +800,67 @@ enum AnimState {
     Moving(AnimStateMoving),
 }
 
+impl AnimState {
+    /// The state playing the animation of the given name.
+    fn from_name(name: &str) -> Option<Self> {
+        use AnimStateMoving::*;
+        use AnimStateScratch::*;
+
+        [
+            AnimState::Idle(AnimStateIdle::Idle),
+            AnimState::Idle(AnimStateIdle::Tired),
+            AnimState::Idle(AnimStateIdle::Sleeping),
+            AnimState::Alert,
+        ]
+        .into_iter()
+        .chain(
+            [
+                ScratchSelf,
+                ScratchWallN,
+                ScratchWallS,
+                ScratchWallE,
+                ScratchWallW,
+            ]
+            .map(|scratch| AnimState::Idle(AnimStateIdle::Scratch(scratch))),
+        )
+        .chain([N, NE, E, SE, S, SW, W, NW].map(AnimState::Moving))
+        .find(|state| state.name() == name)
+    }
+
+    /// The name of this state's animation in sprite sheet manifests.
+    fn name(self) -> &'static str {
+        match self {
+            AnimState::Idle(AnimStateIdle::Idle) => "idle",
+            AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchSelf)) => {
+                "scratch_self"
+            }
+            AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchWallN)) => {
+                "scratch_wall_n"
+            }
+            AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchWallS)) => {
+                "scratch_wall_s"
+            }
+            AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchWallE)) => {
+                "scratch_wall_e"
+            }
+            AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchWallW)) => {
+                "scratch_wall_w"
+            }
+            AnimState::Idle(AnimStateIdle::Tired) => "tired",
+            AnimState::Idle(AnimStateIdle::Sleeping) => "sleeping",
+            AnimState::Alert => "alert",
+            AnimState::Moving(AnimStateMoving::N) => "run_n",
+            AnimState::Moving(AnimStateMoving::NE) => "run_ne",
+            AnimState::Moving(AnimStateMoving::E) => "run_e",
+            AnimState::Moving(AnimStateMoving::SE) => "run_se",
+            AnimState::Moving(AnimStateMoving::S) => "run_s",
+            AnimState::Moving(AnimStateMoving::SW) => "run_sw",
+            AnimState::Moving(AnimStateMoving::W) => "run_w",
+            AnimState::Moving(AnimStateMoving::NW) => "run_nw",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum AnimStateIdle {
     Idle,
@@ -217,6 +891,20 @@ enum AnimStateMoving {
 }
 
 impl AnimStateMoving {
+    /// The direction as a screen space vector, y pointing down.
+    fn vector(self) -> (i32, i32) {
+        match self {
+            AnimStateMoving::N => (0, -1),
+            AnimStateMoving::NE => (1, -1),
+            AnimStateMoving::E => (1, 0),
+            AnimStateMoving::SE => (1, 1),
+            AnimStateMoving::S => (0, 1),
+            AnimStateMoving::SW => (-1, 1),
+            AnimStateMoving::W => (-1, 0),
+            AnimStateMoving::NW => (-1, -1),
+        }
+    }
+
     fn from_vector((x, y): (i32, i32)) -> Self {
         enum AxisDirection {
             Neg,
@@ -253,76 +941,3 @@ impl AnimStateMoving {
         }
     }
 }
-
-fn get_animation(state: AnimState) -> Animation {
-    match state {
-        AnimState::Idle(AnimStateIdle::Idle) => Animation {
-            frames: &[FrameRef(3, 3)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchSelf)) => Animation {
-            frames: &[FrameRef(5, 0), FrameRef(6, 0), FrameRef(7, 0)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchWallN)) => Animation {
-            frames: &[FrameRef(0, 0), FrameRef(0, 1)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchWallS)) => Animation {
-            frames: &[FrameRef(7, 1), FrameRef(6, 2)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchWallE)) => Animation {
-            frames: &[FrameRef(2, 2), FrameRef(2, 3)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Idle(AnimStateIdle::Scratch(AnimStateScratch::ScratchWallW)) => Animation {
-            frames: &[FrameRef(4, 0), FrameRef(4, 1)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Idle(AnimStateIdle::Tired) => Animation {
-            frames: &[FrameRef(3, 2)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Idle(AnimStateIdle::Sleeping) => Animation {
-            frames: &[FrameRef(2, 0), FrameRef(2, 1)],
-            interval: Duration::from_millis(400),
-        },
-        AnimState::Alert => Animation {
-            frames: &[FrameRef(7, 3)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Moving(AnimStateMoving::N) => Animation {
-            frames: &[FrameRef(1, 2), FrameRef(1, 3)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Moving(AnimStateMoving::NE) => Animation {
-            frames: &[FrameRef(0, 2), FrameRef(0, 3)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Moving(AnimStateMoving::E) => Animation {
-            frames: &[FrameRef(3, 0), FrameRef(3, 1)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Moving(AnimStateMoving::SE) => Animation {
-            frames: &[FrameRef(5, 1), FrameRef(5, 2)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Moving(AnimStateMoving::S) => Animation {
-            frames: &[FrameRef(6, 3), FrameRef(7, 2)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Moving(AnimStateMoving::SW) => Animation {
-            frames: &[FrameRef(5, 3), FrameRef(6, 1)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Moving(AnimStateMoving::W) => Animation {
-            frames: &[FrameRef(4, 2), FrameRef(4, 3)],
-            interval: Duration::from_millis(100),
-        },
-        AnimState::Moving(AnimStateMoving::NW) => Animation {
-            frames: &[FrameRef(1, 0), FrameRef(1, 1)],
-            interval: Duration::from_millis(100),
-        },
-    }
-}
@@ -1,14 +1,14 @@
 use std::rc::Rc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use image::GenericImageView;
-use rand::Rng;
 use winit::dpi::PhysicalPosition;
 use winit::event::{ElementState, WindowEvent};
 use winit::window::WindowId;
 use winit::{
     dpi::PhysicalSize,
     event_loop::ActiveEventLoop,
+    monitor::MonitorHandle,
     window::{Window, WindowLevel},
 };
 
@@ -18,35 +18,88 @@ use winit::platform::windows::WindowAttributesExtWindows;
 #[cfg(target_os = "linux")]
 use winit::platform::x11::{WindowAttributesExtX11, WindowType};
 
+use crate::error::{Context, Error, Result};
 use crate::fill;
 use crate::native_utils::get_cursor_position;
-use crate::oneko::Oneko;
+use crate::oneko::{self, Oneko};
+use crate::spawn::{self, Placement};
+#[cfg(target_os = "linux")]
+use crate::workspace::{self, Workspaces};
+
+/// How long to wait before trying again after a tick had to be skipped.
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
 
 pub struct OnekoWindow {
     window: Rc<Window>,
     oneko: Oneko,
     next_update: Instant,
+    /// Set once a leaving cat has run off every monitor.
+    off_screen: bool,
+    /// The desktop the window is pinned to, for the workspace modes that pin it to one.
+    desktop: Option<u32>,
 }
 
-impl OnekoWindow {
-    pub fn new(event_loop: &ActiveEventLoop) -> Self {
-        let oneko = Oneko::default();
+impl Drop for OnekoWindow {
+    fn drop(&mut self) {
+        fill::cleanup_window(&self.window);
+    }
+}
 
-        let mut rng = rand::thread_rng();
+impl OnekoWindow {
+    pub fn new(event_loop: &ActiveEventLoop) -> Result<Self> {
+        let mut oneko = Oneko::default();
+        let saved = oneko.restore();
+        let cursor = get_cursor_position(event_loop).ok();
 
-        let monitor = event_loop
-            .primary_monitor()
+        let saved_monitor = saved
+            .as_ref()
+            .and_then(|saved| saved.monitor.as_ref())
+            .and_then(|name| {
+                event_loop
+                    .available_monitors()
+                    .find(|monitor| monitor.name().as_ref() == Some(name))
+            });
+        let configured_monitor = oneko.spawn().monitor.as_ref().and_then(|name| {
+            event_loop
+                .available_monitors()
+                .find(|monitor| monitor.name().as_ref() == Some(name))
+        });
+        let cursor_monitor = cursor
+            .filter(|_| oneko.spawn().at == Placement::Cursor)
+            .and_then(|cursor| {
+                event_loop.available_monitors().find(|monitor| {
+                    let (position, size) = (monitor.position(), monitor.size());
+                    (position.x..position.x + size.width as i32).contains(&cursor.x)
+                        && (position.y..position.y + size.height as i32).contains(&cursor.y)
+                })
+            });
+        let monitor = saved_monitor
+            .or(configured_monitor)
+            .or(cursor_monitor)
+            .or_else(|| event_loop.primary_monitor())
             .or_else(|| event_loop.available_monitors().next())
-            .expect("Could not find any monitors");
+            .ok_or(Error::NoMonitors)?;
+        oneko.set_scale_factor(monitor.scale_factor());
+        oneko.set_monitor(monitor.name());
 
         let monitor_position = monitor.position();
         let monitor_size = monitor.size();
         let (window_width, window_height) = oneko.get_frame().dimensions();
-        let position = PhysicalPosition::new(
-            rng.gen_range(50..(monitor_size.width - 50 - window_width) as i32) + monitor_position.x,
-            rng.gen_range(50..(monitor_size.height - 50 - window_height) as i32)
-                + monitor_position.y,
-        );
+        let position = match &saved {
+            Some(saved) => {
+                let (x, y) = saved.position_within(
+                    monitor_size.into(),
+                    (window_width as i32, window_height as i32),
+                );
+                PhysicalPosition::new(x + monitor_position.x, y + monitor_position.y)
+            }
+            None => {
+                let cursor = cursor
+                    .map(|cursor| (cursor.x - monitor_position.x, cursor.y - monitor_position.y));
+                let (x, y) = spawn::place(&mut oneko, monitor_size.into(), cursor);
+                PhysicalPosition::new(x + monitor_position.x, y + monitor_position.y)
+            }
+        };
 
         let window_attributes = Window::default_attributes()
             .with_title("oneko")
@@ -65,43 +118,78 @@ impl OnekoWindow {
         #[cfg(target_os = "linux")]
         let window_attributes = window_attributes.with_x11_window_type(vec![WindowType::Utility]);
 
-        let window = event_loop.create_window(window_attributes).unwrap();
+        let window = event_loop
+            .create_window(window_attributes)
+            .context("creating the window")?;
         window
             .set_cursor_hittest(false)
-            .expect("Error disabling hit test");
+            .context("disabling hit test")?;
         window.set_window_level(WindowLevel::AlwaysOnTop);
 
-        Self {
+        #[cfg(target_os = "linux")]
+        let desktop = match oneko::workspaces() {
+            Workspaces::Wm => None,
+            Workspaces::Sticky => {
+                workspace::move_window(&window, workspace::ALL_DESKTOPS);
+                None
+            }
+            Workspaces::Follow | Workspaces::PerWorkspace => {
+                let desktop = workspace::current_desktop();
+                if let Some(desktop) = desktop {
+                    workspace::move_window(&window, desktop);
+                }
+                desktop
+            }
+        };
+        #[cfg(not(target_os = "linux"))]
+        let desktop = None;
+
+        Ok(Self {
             window: Rc::new(window),
             oneko,
             next_update: Instant::now(),
-        }
+            off_screen: false,
+            desktop,
+        })
     }
 
     pub fn update(&mut self) {
-        let mut cursor_pos: (i32, i32) = get_cursor_position(&self.window)
-            .expect("Error getting cursor position")
-            .into();
-
-        let mut window_position: (i32, i32) = self
-            .window
-            .outer_position()
-            .expect("Error getting window position")
-            .into();
+        // Pointers and monitors come and go with docks and display servers restarting, so a tick
+        // where either is missing is tried again a little later.
+        let (Ok(cursor_pos), Ok(window_position)) = (
+            get_cursor_position(&self.window),
+            self.window.outer_position(),
+        ) else {
+            self.next_update = Instant::now() + RETRY_INTERVAL;
+            return;
+        };
+        let mut cursor_pos: (i32, i32) = cursor_pos.into();
+        let mut window_position: (i32, i32) = window_position.into();
 
-        let monitor = self
-            .window
-            .current_monitor()
-            .expect("Couldn't get current monitor");
+        let Some(monitor) = self.monitor(window_position) else {
+            if self.oneko.leaving() {
+                self.off_screen = true;
+            } else {
+                self.relocate();
+            }
+            return;
+        };
 
         let monitor_position: (i32, i32) = monitor.position().into();
         let monitor_size: (i32, i32) = monitor.size().into();
+        self.oneko.set_scale_factor(monitor.scale_factor());
+        self.oneko.set_monitor(monitor.name());
 
         cursor_pos.0 -= monitor_position.0;
         cursor_pos.1 -= monitor_position.1;
         window_position.0 -= monitor_position.0;
         window_position.1 -= monitor_position.1;
 
+        #[cfg(target_os = "linux")]
+        if self.follow_desktop() {
+            window_position = spawn::run_in(&mut self.oneko, monitor_size);
+        }
+
         let (update_delay, mut new_window_position) =
             self.oneko.act(window_position, cursor_pos, monitor_size);
         self.next_update = Instant::now() + update_delay;
@@ -117,6 +205,71 @@ impl OnekoWindow {
         }
     }
 
+    /// The connected monitor the window at `position` is on, if any part of it still is. winit
+    /// keeps answering with the last monitor a window was on after that monitor is unplugged, so
+    /// its answer only counts while the monitor is still listed and under the window.
+    fn monitor(&self, position: (i32, i32)) -> Option<MonitorHandle> {
+        let (width, height): (i32, i32) = self.window.outer_size().into();
+        let overlaps = |monitor: &MonitorHandle| {
+            let (x, y): (i32, i32) = monitor.position().into();
+            let (monitor_width, monitor_height): (i32, i32) = monitor.size().into();
+            position.0 < x + monitor_width
+                && x < position.0 + width
+                && position.1 < y + monitor_height
+                && y < position.1 + height
+        };
+        let monitors: Vec<_> = self.window.available_monitors().filter(overlaps).collect();
+        self.window
+            .current_monitor()
+            .filter(|current| monitors.contains(current))
+            .or_else(|| monitors.into_iter().next())
+    }
+
+    /// Moves a cat whose monitor went away onto the primary one, or the first one left, as
+    /// close to where it was as that monitor allows.
+    fn relocate(&mut self) {
+        self.next_update = Instant::now() + RETRY_INTERVAL;
+        let Some(monitor) = self
+            .window
+            .primary_monitor()
+            .or_else(|| self.window.available_monitors().next())
+        else {
+            return;
+        };
+        let Ok(position) = self.window.outer_position() else {
+            return;
+        };
+        let (width, height): (i32, i32) = self.window.outer_size().into();
+        let (monitor_x, monitor_y): (i32, i32) = monitor.position().into();
+        let (monitor_width, monitor_height): (i32, i32) = monitor.size().into();
+        let x = position
+            .x
+            .clamp(monitor_x, monitor_x + (monitor_width - width).max(0));
+        let y = position
+            .y
+            .clamp(monitor_y, monitor_y + (monitor_height - height).max(0));
+        self.window.set_outer_position(PhysicalPosition::new(x, y));
+    }
+
+    /// Takes a following cat along to the desktop being shown, returning whether it moved and
+    /// should run in again.
+    #[cfg(target_os = "linux")]
+    fn follow_desktop(&mut self) -> bool {
+        if oneko::workspaces() != Workspaces::Follow || self.oneko.leaving() {
+            return false;
+        }
+        let Some(current) = workspace::current_desktop() else {
+            return false;
+        };
+        if self.desktop == Some(current) {
+            return false;
+        }
+        workspace::move_window(&self.window, current);
+        tracing::info!(desktop = current, "following to desktop");
+        // A cat that never knew its desktop only gets pinned, it did not go anywhere.
+        self.desktop.replace(current).is_some()
+    }
+
     pub fn next_update(&self) -> Instant {
         self.next_update
     }
@@ -125,14 +278,40 @@ impl OnekoWindow {
         self.window.id()
     }
 
+    /// The desktop the window is pinned to, if it is pinned to one.
+    pub fn desktop(&self) -> Option<u32> {
+        self.desktop
+    }
+
+    /// Whether the cat has been taken away over the control socket.
+    pub fn removed(&self) -> bool {
+        self.oneko.removed()
+    }
+
+    /// Starts the cat's exit animation.
+    pub fn leave(&mut self) {
+        self.oneko.leave();
+        self.next_update = Instant::now();
+    }
+
+    /// Whether the cat is done leaving and the window can go.
+    pub fn gone(&self) -> bool {
+        self.off_screen || self.oneko.gone()
+    }
+
     pub(crate) fn handle_window_event(&mut self, event: &WindowEvent) {
         match event {
-            WindowEvent::MouseInput { state, .. } => {
-                if *state == ElementState::Pressed {
-                    self.oneko.click();
-                    self.next_update = Instant::now();
-                    self.window.request_redraw();
-                }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                ..
+            } => {
+                self.oneko.click();
+                self.next_update = Instant::now();
+                self.window.request_redraw();
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.oneko.set_scale_factor(*scale_factor);
+                self.window.request_redraw();
             }
             WindowEvent::RedrawRequested => {
                 let data = self.oneko.get_frame();
@@ -0,0 +1,189 @@
+//! Remembering the cat across restarts.
+//!
+//! Where the cat was, what it was doing and a few long-term stats go to
+//! `$XDG_STATE_HOME/oneko-rust/state.toml` every minute and on exit, and the first cat of the next
+//! run picks up from there, so logging out and back in finds it asleep where it was.
+//!
+//! Only backends placing the cat on the desktop restore its position. Wayland compositors pick
+//! the output themselves, so there the cat only returns to the same spot on whichever output it
+//! gets.
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// How often the state is written out while the cat runs.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The cat as it was when the state was last saved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedCat {
+    /// The name of the monitor the cat was on, if the backend knows it.
+    pub monitor: Option<String>,
+    /// The top left corner of the cat, relative to that monitor.
+    pub position: (i32, i32),
+    /// The animation it was playing.
+    pub state: String,
+    /// Whether it had been put to sleep over the control socket.
+    #[serde(default)]
+    pub asleep: bool,
+}
+
+impl SavedCat {
+    /// Where to put the cat back on a monitor of the given size, which may have shrunk since.
+    pub fn position_within(
+        &self,
+        (width, height): (i32, i32),
+        (cat_width, cat_height): (i32, i32),
+    ) -> (i32, i32) {
+        (
+            self.position.0.clamp(0, (width - cat_width).max(0)),
+            self.position.1.clamp(0, (height - cat_height).max(0)),
+        )
+    }
+}
+
+/// Counters kept for as long as the state file lives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    /// Pixels run, at the scale the cat was drawn at.
+    pub distance: f64,
+    pub naps: u64,
+    pub clicks: u64,
+    pub wall_scratches: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct State {
+    cat: Option<SavedCat>,
+    stats: Stats,
+}
+
+struct Persist {
+    path: PathBuf,
+    state: State,
+    /// The saved cat is handed to the first cat asking for it, and only that one is saved.
+    claimed: bool,
+    /// What is on disk, to skip writing the same thing again.
+    written: State,
+}
+
+static PERSIST: Mutex<Option<Persist>> = Mutex::new(None);
+
+/// `$XDG_STATE_HOME/oneko-rust`, falling back to `~/.local/state`.
+pub fn state_dir() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state")))
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(dir.join("oneko-rust"))
+}
+
+/// `state.toml` in [`state_dir`].
+pub fn default_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("state.toml"))
+}
+
+/// Reads the saved state and starts saving it every so often on a background thread. Until this
+/// is called nothing is remembered, which keeps recordings and screensavers out of it.
+pub fn start() {
+    let Some(path) = default_path() else {
+        return;
+    };
+    let state = match fs::read_to_string(&path) {
+        Ok(text) => toml::from_str(&text).unwrap_or_else(|err| {
+            tracing::warn!("Error parsing {}, starting afresh: {err}", path.display());
+            State::default()
+        }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => State::default(),
+        Err(err) => {
+            tracing::warn!("Error reading {}: {err}", path.display());
+            State::default()
+        }
+    };
+    *PERSIST.lock().unwrap() = Some(Persist {
+        path,
+        written: state.clone(),
+        state,
+        claimed: false,
+    });
+
+    thread::Builder::new()
+        .name("persist".into())
+        .spawn(|| loop {
+            thread::sleep(SAVE_INTERVAL);
+            save();
+        })
+        .expect("Error spawning persist thread");
+}
+
+/// Hands out the saved cat to the first cat to ask, making that the cat that is saved from now
+/// on. That cat gets `Some`, holding `None` if nothing was saved yet. Every other cat gets
+/// `None`, as do all of them when nothing is being remembered.
+pub fn claim() -> Option<Option<SavedCat>> {
+    let mut persist = PERSIST.lock().unwrap();
+    let persist = persist.as_mut().filter(|persist| !persist.claimed)?;
+    persist.claimed = true;
+    Some(persist.state.cat.clone())
+}
+
+/// Records where the claiming cat is now.
+pub fn update(cat: SavedCat) {
+    if let Some(persist) = &mut *PERSIST.lock().unwrap() {
+        persist.state.cat = Some(cat);
+    }
+}
+
+/// Bumps the long-term stats.
+pub fn count(f: impl FnOnce(&mut Stats)) {
+    if let Some(persist) = &mut *PERSIST.lock().unwrap() {
+        f(&mut persist.state.stats);
+    }
+}
+
+/// The long-term stats so far, if anything is being remembered.
+pub fn stats() -> Option<Stats> {
+    PERSIST
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|persist| persist.state.stats)
+}
+
+/// Writes the state out if it changed since the last time.
+pub fn save() {
+    let mut persist = PERSIST.lock().unwrap();
+    let Some(persist) = persist.as_mut() else {
+        return;
+    };
+    if persist.state == persist.written {
+        return;
+    }
+    match write_atomically(&persist.path, &persist.state) {
+        Ok(()) => persist.written = persist.state.clone(),
+        Err(err) => tracing::warn!("Error saving {}: {err}", persist.path.display()),
+    }
+}
+
+/// Writes to a file next to `path` first and renames it over `path`, so a crash halfway leaves
+/// the old state rather than half of the new one.
+fn write_atomically(path: &Path, state: &State) -> io::Result<()> {
+    let text = toml::to_string(state).map_err(io::Error::other)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("toml.tmp");
+    let mut file = fs::File::create(&temp)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
@@ -0,0 +1,153 @@
+//! Recoloring sprite sheets as they load.
+//!
+//! The classic X11 oneko drew a two-color bitmap and let `-fg`, `-bg` and `-rv` pick the colors.
+//! Sheets here are full color, so the same flags map the art onto a ramp from the foreground
+//! (outline) color to the background (fur) color by brightness. Pink and purple accents such as
+//! the nose, ears and mouth lean towards blue where fur and outlines lean towards red, which is
+//! how they are told apart and kept as they are.
+
+use std::collections::BTreeMap;
+
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::parse_color;
+
+/// An opaque `#rrggbb` color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub [u8; 3]);
+
+impl From<Color> for String {
+    fn from(Color([r, g, b]): Color) -> Self {
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(color: String) -> Result<Self, Self::Error> {
+        parse_color(&color)
+            .map(|Rgba([r, g, b, _])| Color([r, g, b]))
+            .ok_or_else(|| format!("invalid color {color:?}, expected #rrggbb"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    /// A black cat with a light outline.
+    Kuroneko,
+}
+
+impl Variant {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "kuroneko" => Some(Variant::Kuroneko),
+            _ => None,
+        }
+    }
+
+    /// The `(foreground, background)` ramp the variant maps the sheet onto.
+    fn colors(self) -> (Color, Color) {
+        match self {
+            Variant::Kuroneko => (Color([150, 150, 160]), Color([28, 28, 32])),
+        }
+    }
+}
+
+/// The `[colors]` table of the config, which command line flags are layered on top of.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Recolor {
+    /// Exact color replacements, applied before anything else.
+    pub palette: BTreeMap<Color, Color>,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    /// Swaps the foreground and background colors.
+    pub reverse: bool,
+    pub variant: Option<Variant>,
+}
+
+impl Recolor {
+    /// Layers the settings from `other` over these ones.
+    pub fn merge(&mut self, other: &Recolor) {
+        self.palette
+            .extend(other.palette.iter().map(|(&from, &to)| (from, to)));
+        self.fg = other.fg.or(self.fg);
+        self.bg = other.bg.or(self.bg);
+        self.reverse |= other.reverse;
+        self.variant = other.variant.or(self.variant);
+    }
+
+    /// The `(foreground, background)` ramp to map the sheet onto, if any.
+    fn ramp(&self) -> Option<(Color, Color)> {
+        let (fg, bg) = match (self.variant, self.fg, self.bg) {
+            (None, None, None) if !self.reverse => return None,
+            (Some(variant), fg, bg) => {
+                let (variant_fg, variant_bg) = variant.colors();
+                (fg.unwrap_or(variant_fg), bg.unwrap_or(variant_bg))
+            }
+            (None, fg, bg) => (
+                fg.unwrap_or(Color([0, 0, 0])),
+                bg.unwrap_or(Color([255, 255, 255])),
+            ),
+        };
+        Some(if self.reverse { (bg, fg) } else { (fg, bg) })
+    }
+
+    pub fn apply(&self, image: &mut RgbaImage) {
+        for pixel in image.pixels_mut() {
+            let Rgba([r, g, b, a]) = *pixel;
+            if let Some(&Color([r, g, b])) = self.palette.get(&Color([r, g, b])) {
+                *pixel = Rgba([r, g, b, a]);
+            }
+        }
+
+        let Some((fg, bg)) = self.ramp() else {
+            return;
+        };
+
+        // Stretch the sheet's own brightness range over the whole ramp, so the darkest outline
+        // gets exactly the foreground color and the lightest fur exactly the background.
+        let (min, max) = image
+            .pixels()
+            .filter(|pixel| pixel[3] != 0 && !is_accent(**pixel))
+            .map(|&pixel| luma(pixel))
+            .fold((f32::MAX, f32::MIN), |(min, max), luma| {
+                (min.min(luma), max.max(luma))
+            });
+        let range = (max - min).max(f32::EPSILON);
+
+        for pixel in image.pixels_mut() {
+            if pixel[3] == 0 || is_accent(*pixel) {
+                continue;
+            }
+            let t = (luma(*pixel) - min) / range;
+            let channel = |index: usize| {
+                let (from, to) = (fg.0[index] as f32, bg.0[index] as f32);
+                (from + (to - from) * t).round() as u8
+            };
+            *pixel = Rgba([channel(0), channel(1), channel(2), pixel[3]]);
+        }
+    }
+}
+
+fn luma(Rgba([r, g, b, _]): Rgba<u8>) -> f32 {
+    (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0
+}
+
+fn is_accent(Rgba([_, g, b, _]): Rgba<u8>) -> bool {
+    b as i32 > g as i32 + 16
+}
+
+/// Multiplies every pixel by `color`, like looking at the cat through colored cellophane. Light
+/// fur takes on the color while outlines stay dark.
+pub fn tint(image: &mut RgbaImage, Color(color): Color) {
+    for pixel in image.pixels_mut() {
+        for (channel, tint) in pixel.0.iter_mut().zip(color) {
+            *channel = (*channel as u32 * tint as u32 / 255) as u8;
+        }
+    }
+}
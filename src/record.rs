@@ -0,0 +1,210 @@
+//! Record an animated GIF or APNG of a simulated cat session.
+//!
+//! The cat chases a roaming target across a virtual screen on a simulated clock, so a recording
+//! of any length is written as fast as it can be encoded. Every frame is shown for exactly as long
+//! as the animation would have shown it, which is the `Animation::interval` returned by
+//! `Oneko::act`.
+//!
+//! With `--debug`, every frame also shows what the cat was thinking: its state and frame count,
+//! the follow radius around it, the target it is heading for and the wall it believes it is
+//! touching.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, GenericImageView, ImageError, Rgba, RgbaImage,
+};
+
+use crate::font;
+use crate::oneko::{Oneko, Step};
+use crate::target::Roam;
+use crate::trail::Trail;
+
+const DEBUG_RADIUS: Rgba<u8> = Rgba([60, 160, 255, 255]);
+const DEBUG_TARGET: Rgba<u8> = Rgba([230, 40, 40, 255]);
+const DEBUG_WALL: Rgba<u8> = Rgba([255, 170, 0, 255]);
+const DEBUG_TEXT: Rgba<u8> = Rgba([255, 0, 255, 255]);
+
+pub struct RecordOptions {
+    pub output: PathBuf,
+    pub duration: Duration,
+    pub size: (u32, u32),
+    /// The background color, or `None` for a transparent background.
+    pub background: Option<Rgba<u8>>,
+    /// Draw the cat's state over every frame.
+    pub debug: bool,
+}
+
+/// One rendered frame and how long it stays on screen.
+struct RecordedFrame {
+    image: RgbaImage,
+    delay: Duration,
+}
+
+fn simulate(options: &RecordOptions) -> Vec<RecordedFrame> {
+    let (width, height) = (options.size.0 as i32, options.size.1 as i32);
+    let background = options.background.unwrap_or(Rgba([0, 0, 0, 0]));
+
+    let mut oneko = Oneko::default();
+    let mut roam = Roam::new((width, height));
+    let mut trail = Trail::default();
+    let (cat_width, cat_height) = oneko.get_frame().dimensions();
+    let mut position = (
+        (width - cat_width as i32).max(0) / 2,
+        (height - cat_height as i32).max(0) / 2,
+    );
+
+    let start = Instant::now();
+    let mut elapsed = Duration::ZERO;
+    let mut frames: Vec<RecordedFrame> = Vec::new();
+    while elapsed < options.duration {
+        let target = roam.target_at((width, height), start + elapsed);
+        let (interval, new_position) = oneko.act(position, target, (width, height));
+        position = new_position;
+        let now = start + elapsed;
+        elapsed += interval;
+        if let Some(step) = oneko.last_step() {
+            trail.update(&step, oneko.trail(), now);
+        }
+
+        let mut image = RgbaImage::from_pixel(options.size.0, options.size.1, background);
+        for (sprite, (x, y)) in trail.sprites(oneko.trail(), now) {
+            image::imageops::overlay(&mut image, &sprite, x.into(), y.into());
+        }
+        image::imageops::overlay(
+            &mut image,
+            &*oneko.get_frame(),
+            position.0.into(),
+            position.1.into(),
+        );
+        if let Some(step) = oneko.last_step().filter(|_| options.debug) {
+            draw_debug_overlay(&mut image, &step);
+        }
+
+        // A sleeping or idle cat produces long runs of identical frames.
+        match frames.last_mut() {
+            Some(last) if last.image == image => last.delay += interval,
+            _ => frames.push(RecordedFrame {
+                image,
+                delay: interval,
+            }),
+        }
+    }
+
+    frames
+}
+
+fn put(image: &mut RgbaImage, (x, y): (i32, i32), color: Rgba<u8>) {
+    if x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height() {
+        image.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+fn draw_debug_overlay(image: &mut RgbaImage, step: &Step) {
+    let (x, y) = step.position;
+    let (width, height) = step.size;
+    let (center_x, center_y) = (x + width / 2, y + height / 2);
+
+    let radius = step.follow_distance;
+    let points = (radius * std::f64::consts::TAU).ceil().max(1.0) as u32;
+    for point in 0..points {
+        let angle = point as f64 / points as f64 * std::f64::consts::TAU;
+        let offset = (
+            (angle.cos() * radius).round() as i32,
+            (angle.sin() * radius).round() as i32,
+        );
+        put(
+            image,
+            (center_x + offset.0, center_y + offset.1),
+            DEBUG_RADIUS,
+        );
+    }
+
+    let (target_x, target_y) = step.target;
+    for arm in -4..=4 {
+        put(image, (target_x + arm, target_y), DEBUG_TARGET);
+        put(image, (target_x, target_y + arm), DEBUG_TARGET);
+    }
+
+    // Outline the side of the cat that is against the wall.
+    let edge: Vec<(i32, i32)> = match step.wall {
+        Some("north") => (x..x + width).map(|px| (px, y)).collect(),
+        Some("east") => (y..y + height).map(|py| (x + width - 1, py)).collect(),
+        Some("south") => (x..x + width).map(|px| (px, y + height - 1)).collect(),
+        Some("west") => (y..y + height).map(|py| (x, py)).collect(),
+        _ => Vec::new(),
+    };
+    for point in edge {
+        put(image, point, DEBUG_WALL);
+    }
+
+    let lines = [
+        format!("{} {}", step.state, step.frame_count),
+        format!("distance {:.0}/{:.0}", step.distance, step.follow_distance),
+        format!("wall {}", step.wall.unwrap_or("none")),
+    ];
+    for (index, line) in lines.iter().enumerate() {
+        let line_y = 2 + index as i32 * (font::GLYPH_HEIGHT as i32 + 2);
+        font::draw_text(image, line, (2, line_y), 1, DEBUG_TEXT);
+    }
+}
+
+fn write_gif(path: &Path, frames: Vec<RecordedFrame>) -> Result<(), ImageError> {
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(frames.into_iter().map(|frame| {
+        Frame::from_parts(
+            frame.image,
+            0,
+            0,
+            Delay::from_saturating_duration(frame.delay),
+        )
+    }))
+}
+
+fn write_apng(
+    path: &Path,
+    (width, height): (u32, u32),
+    frames: Vec<RecordedFrame>,
+) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        let millis = frame.delay.as_millis().min(u16::MAX.into()) as u16;
+        writer.set_frame_delay(millis, 1000)?;
+        writer.write_image_data(frame.image.as_raw())?;
+    }
+    writer.finish()
+}
+
+pub fn run(options: RecordOptions) -> io::Result<()> {
+    let frames = simulate(&options);
+    let frame_count = frames.len();
+
+    let is_gif = options
+        .output
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+    if is_gif {
+        write_gif(&options.output, frames).map_err(io::Error::other)?;
+    } else {
+        write_apng(&options.output, options.size, frames).map_err(io::Error::other)?;
+    }
+
+    println!(
+        "Wrote {frame_count} frames ({:.1}s) to {}",
+        options.duration.as_secs_f64(),
+        options.output.display()
+    );
+    Ok(())
+}
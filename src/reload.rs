@@ -0,0 +1,143 @@
+//! Swaps in a new theme whenever the config, sprite sheet manifest, sheet image, accessories or
+//! XBM character files change on disk.
+//!
+//! Directories are watched rather than the files themselves, since most editors save by writing a
+//! new file and renaming it over the old one. Which files those are follows the [`config::source`],
+//! so a launch forwarded with another config moves the watches over to it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+use inotify::{Inotify, WatchDescriptor, WatchMask, Watches};
+
+use crate::config::{self, Config, Overrides};
+
+/// Editors often touch a file several times per save, so wait for things to settle down.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// What is being watched, shared between the reload thread and whoever changes the config.
+struct Watched {
+    watches: Watches,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+static WATCHED: Mutex<Option<Watched>> = Mutex::new(None);
+
+/// Starts watching the files behind the current config on a background thread.
+pub fn watch() {
+    let inotify = match Inotify::init() {
+        Ok(inotify) => inotify,
+        Err(err) => {
+            tracing::error!("Hot reload stopped: {err}");
+            return;
+        }
+    };
+    *WATCHED.lock().unwrap() = Some(Watched {
+        watches: inotify.watches(),
+        dirs: HashMap::new(),
+        files: Vec::new(),
+    });
+    update_watches();
+
+    thread::Builder::new()
+        .name("reload".into())
+        .spawn(move || {
+            if let Err(err) = run(inotify) {
+                tracing::error!("Hot reload stopped: {err}");
+            }
+        })
+        .expect("Error spawning reload thread");
+}
+
+fn watched_files(config_path: &Path, overrides: &Overrides) -> Vec<PathBuf> {
+    let mut config = Config::load(config_path).unwrap_or_default();
+    if let Some(character) = &overrides.character {
+        config.character = Some(character.clone());
+    }
+    config.files(config_path)
+}
+
+/// Points the watches at the files behind the current config, which may name other files after a
+/// reload or be another config altogether after a launch.
+pub fn update_watches() {
+    let source = config::source();
+    let files = source
+        .path
+        .as_deref()
+        .map(|path| watched_files(path, &source.overrides))
+        .unwrap_or_default();
+    let wanted: HashSet<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+
+    let mut watched = WATCHED.lock().unwrap();
+    let Some(Watched {
+        watches,
+        dirs,
+        files: watched_files,
+    }) = watched.as_mut()
+    else {
+        return;
+    };
+    dirs.retain(|wd, dir| {
+        let keep = wanted.contains(dir.as_path());
+        if !keep {
+            let _ = watches.remove(wd.clone());
+        }
+        keep
+    });
+    for dir in wanted {
+        if dirs.values().any(|watched| watched == dir) {
+            continue;
+        }
+        let mask =
+            WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE | WatchMask::DELETE;
+        match watches.add(dir, mask) {
+            Ok(wd) => {
+                dirs.insert(wd, dir.to_path_buf());
+            }
+            Err(err) => tracing::warn!("Error watching {}: {err}", dir.display()),
+        }
+    }
+    *watched_files = files;
+}
+
+fn run(mut inotify: Inotify) -> io::Result<()> {
+    let mut buffer = [0; 4096];
+
+    loop {
+        let events = inotify.read_events_blocking(&mut buffer)?;
+        let changed = {
+            let watched = WATCHED.lock().unwrap();
+            let Some(watched) = watched.as_ref() else {
+                return Ok(());
+            };
+            events.into_iter().any(|event| {
+                let (Some(dir), Some(name)) = (watched.dirs.get(&event.wd), event.name) else {
+                    return false;
+                };
+                watched.files.contains(&dir.join(name))
+            })
+        };
+        if !changed {
+            continue;
+        }
+
+        thread::sleep(SETTLE_TIME);
+        while inotify.read_events(&mut buffer).is_ok() {}
+
+        let source = config::source();
+        if config::apply(source.path.as_deref(), &source.overrides) {
+            if let Some(path) = &source.path {
+                tracing::info!("Reloaded {}", path.display());
+            }
+        }
+        // The config may point at a different sheet now, so look again after every reload.
+        update_watches();
+    }
+}
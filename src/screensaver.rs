@@ -0,0 +1,382 @@
+//! A screensaver filling the screen with a herd of roaming cats.
+//!
+//! The hack follows the XScreenSaver conventions: it draws into the window named by `-window-id`
+//! or `$XSCREENSAVER_WINDOW`, into the root window with `-root`, and otherwise opens its own
+//! fullscreen window which closes again on the first key press, click or pointer movement.
+//!
+//! With a `[trail]` in the config every cat leaves paw prints behind on the black background.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::{GenericImageView, Rgba, RgbaImage};
+use rand::Rng;
+use x11rb::{
+    connection::Connection,
+    errors::ConnectionError,
+    protocol::{
+        xproto::{
+            self, ConnectionExt as _, CreateGCAux, CreateWindowAux, EventMask, GrabMode,
+            ImageFormat, Rectangle, WindowClass,
+        },
+        Event,
+    },
+    xcb_ffi::XCBConnection,
+    CURRENT_TIME, NONE,
+};
+
+use crate::control;
+use crate::error::{self, Context, Result};
+use crate::oneko::Oneko;
+use crate::shutdown;
+use crate::target::Roam;
+use crate::trail::Trail;
+use crate::x11_backend::frame_to_bgra;
+
+/// How many cats the screensaver shows unless told otherwise.
+pub const HERD_SIZE: usize = 6;
+/// How far the pointer may move before a standalone screensaver exits.
+const MOTION_THRESHOLD: i32 = 10;
+/// How often to look for spawn requests once every cat has been removed.
+const CONTROL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Where the screensaver draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreensaverWindow {
+    /// A window created by someone else, usually XScreenSaver.
+    Existing(xproto::Window),
+    /// The root window of the default screen.
+    Root,
+    /// A fullscreen window of our own that exits on input.
+    Standalone,
+}
+
+struct Cat {
+    oneko: Oneko,
+    roam: Roam,
+    position: (i32, i32),
+    next_update: Instant,
+    trail: Trail,
+}
+
+impl Cat {
+    fn rect(&self) -> Rectangle {
+        let (width, height) = self.oneko.get_frame().dimensions();
+        Rectangle {
+            x: self.position.0 as i16,
+            y: self.position.1 as i16,
+            width: width as u16,
+            height: height as u16,
+        }
+    }
+}
+
+/// Where a sprite with its top left corner at `(x, y)` goes.
+fn sprite_rect(sprite: &RgbaImage, (x, y): (i32, i32)) -> Rectangle {
+    Rectangle {
+        x: x as i16,
+        y: y as i16,
+        width: sprite.width() as u16,
+        height: sprite.height() as u16,
+    }
+}
+
+fn union(a: Rectangle, b: Rectangle) -> Rectangle {
+    let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+    let right = (a.x as i32 + a.width as i32).max(b.x as i32 + b.width as i32);
+    let bottom = (a.y as i32 + a.height as i32).max(b.y as i32 + b.height as i32);
+    Rectangle {
+        x,
+        y,
+        width: (right - x as i32) as u16,
+        height: (bottom - y as i32) as u16,
+    }
+}
+
+fn intersects(a: Rectangle, b: Rectangle) -> bool {
+    (a.x as i32) < b.x as i32 + b.width as i32
+        && (b.x as i32) < a.x as i32 + a.width as i32
+        && (a.y as i32) < b.y as i32 + b.height as i32
+        && (b.y as i32) < a.y as i32 + a.height as i32
+}
+
+struct Screensaver {
+    conn: XCBConnection,
+    window: xproto::Window,
+    gc: xproto::Gcontext,
+    depth: u8,
+    size: (i32, i32),
+    cats: Vec<Cat>,
+    /// Where paw prints were drawn last time, to be redrawn as they fade.
+    prints: Vec<Rectangle>,
+    /// How many cats have been spawned so far, which picks the next cat's tint.
+    spawned: usize,
+}
+
+impl Screensaver {
+    fn clear(&self) -> Result<(), ConnectionError> {
+        self.conn.poly_fill_rectangle(
+            self.window,
+            self.gc,
+            &[Rectangle {
+                x: 0,
+                y: 0,
+                width: self.size.0 as u16,
+                height: self.size.1 as u16,
+            }],
+        )?;
+        Ok(())
+    }
+
+    /// Recomposes `rect` from the black background, the paw prints and every cat touching it.
+    #[tracing::instrument(level = "trace", skip(self, prints), err)]
+    fn draw(
+        &self,
+        rect: Rectangle,
+        prints: &[(RgbaImage, (i32, i32))],
+    ) -> Result<(), ConnectionError> {
+        let mut image =
+            RgbaImage::from_pixel(rect.width.into(), rect.height.into(), Rgba([0, 0, 0, 255]));
+        for (print, (x, y)) in prints
+            .iter()
+            .filter(|(print, position)| intersects(sprite_rect(print, *position), rect))
+        {
+            image::imageops::overlay(
+                &mut image,
+                print,
+                (x - rect.x as i32).into(),
+                (y - rect.y as i32).into(),
+            );
+        }
+        for cat in self.cats.iter().filter(|cat| intersects(cat.rect(), rect)) {
+            image::imageops::overlay(
+                &mut image,
+                &*cat.oneko.get_frame(),
+                (cat.position.0 - rect.x as i32).into(),
+                (cat.position.1 - rect.y as i32).into(),
+            );
+        }
+
+        self.conn.put_image(
+            ImageFormat::Z_PIXMAP,
+            self.window,
+            self.gc,
+            rect.width,
+            rect.height,
+            rect.x,
+            rect.y,
+            0,
+            self.depth,
+            &frame_to_bgra(&image.view(0, 0, image.width(), image.height())),
+        )?;
+        Ok(())
+    }
+
+    /// Adds `count` cats at random spots, each tinted after the one before.
+    fn spawn_cats(&mut self, count: usize) {
+        let mut rng = rand::thread_rng();
+        let (width, height) = self.size;
+        for _ in 0..count {
+            let mut oneko = Oneko::default();
+            oneko.set_tint(self.spawned);
+            self.spawned += 1;
+            let (cat_width, cat_height) = oneko.get_frame().dimensions();
+            self.cats.push(Cat {
+                oneko,
+                roam: Roam::new(self.size),
+                position: (
+                    rng.gen_range(0..(width - cat_width as i32).max(1)),
+                    rng.gen_range(0..(height - cat_height as i32).max(1)),
+                ),
+                next_update: Instant::now(),
+                trail: Trail::default(),
+            });
+        }
+    }
+
+    /// Takes away the cats removed over the control socket, returning the areas they were in.
+    fn remove_cats(&mut self) -> Vec<Rectangle> {
+        let (removed, kept) = std::mem::take(&mut self.cats)
+            .into_iter()
+            .partition(|cat| cat.oneko.removed());
+        self.cats = kept;
+        removed.iter().map(Cat::rect).collect::<Vec<_>>()
+    }
+
+    /// Advances every cat that is due and redraws the areas they moved through.
+    fn update(&mut self) {
+        self.spawn_cats(control::take_spawns());
+        let now = Instant::now();
+        let mut dirty = Vec::new();
+        for cat in self.cats.iter_mut().filter(|cat| cat.next_update <= now) {
+            let old_rect = cat.rect();
+            let target = cat.roam.target(self.size);
+            let (update_delay, position) = cat.oneko.act(cat.position, target, self.size);
+            cat.position = position;
+            cat.next_update = now + update_delay;
+            if let Some(step) = cat.oneko.last_step() {
+                cat.trail.update(&step, cat.oneko.trail(), now);
+            }
+            dirty.push(union(old_rect, cat.rect()));
+        }
+        // Cats only find out they were removed when they act, so this comes after.
+        dirty.extend(self.remove_cats());
+
+        // Prints fade a little every frame, so each one shown before or now gets redrawn.
+        let prints: Vec<_> = self
+            .cats
+            .iter()
+            .flat_map(|cat| cat.trail.sprites(cat.oneko.trail(), now))
+            .collect();
+        let print_rects: Vec<_> = prints
+            .iter()
+            .map(|(print, position)| sprite_rect(print, *position))
+            .collect();
+        dirty.extend(std::mem::replace(&mut self.prints, print_rects.clone()));
+        dirty.extend(print_rects);
+
+        let drawn = dirty
+            .into_iter()
+            .try_for_each(|rect| self.draw(rect, &prints))
+            .and_then(|()| self.conn.flush());
+        if let Err(err) = drawn {
+            tracing::warn!("Skipping a frame: {err}");
+        }
+    }
+
+    /// Checks the target window's size, returning whether it changed.
+    #[tracing::instrument(level = "trace", skip(self), ret, err)]
+    fn update_size(&mut self) -> Result<bool> {
+        let geometry = self
+            .conn
+            .get_geometry(self.window)
+            .context("querying the screensaver window")?
+            .reply()
+            .context("querying the screensaver window")?;
+        let size = (geometry.width.into(), geometry.height.into());
+        self.depth = geometry.depth;
+        if size == self.size {
+            return Ok(false);
+        }
+        self.size = size;
+        Ok(true)
+    }
+}
+
+fn create_standalone_window(
+    conn: &XCBConnection,
+    screen: &xproto::Screen,
+) -> Result<xproto::Window> {
+    let window = conn.generate_id().context("allocating a window id")?;
+    conn.create_window(
+        screen.root_depth,
+        window,
+        screen.root,
+        0,
+        0,
+        screen.width_in_pixels,
+        screen.height_in_pixels,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        screen.root_visual,
+        &CreateWindowAux::new()
+            .background_pixel(screen.black_pixel)
+            .override_redirect(1)
+            .event_mask(EventMask::KEY_PRESS | EventMask::BUTTON_PRESS | EventMask::POINTER_MOTION),
+    )
+    .context("creating the screensaver window")?;
+    conn.map_window(window)
+        .context("mapping the screensaver window")?;
+
+    // Without the grabs key presses would still go to whichever window had focus before.
+    let _ = conn.grab_keyboard(true, window, CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC);
+    let _ = conn.grab_pointer(
+        true,
+        window,
+        EventMask::BUTTON_PRESS | EventMask::POINTER_MOTION,
+        GrabMode::ASYNC,
+        GrabMode::ASYNC,
+        window,
+        NONE,
+        CURRENT_TIME,
+    );
+
+    Ok(window)
+}
+
+pub fn run(target: ScreensaverWindow, cat_count: usize) -> Result<()> {
+    let (conn, screen_num) = error::connect("X11", || XCBConnection::connect(None))?;
+    let screen = &conn.setup().roots[screen_num];
+    let window = match target {
+        ScreensaverWindow::Existing(window) => window,
+        ScreensaverWindow::Root => screen.root,
+        ScreensaverWindow::Standalone => create_standalone_window(&conn, screen)?,
+    };
+
+    let gc = conn
+        .generate_id()
+        .context("allocating a graphics context id")?;
+    conn.create_gc(
+        gc,
+        window,
+        &CreateGCAux::new().foreground(screen.black_pixel),
+    )
+    .context("creating a graphics context")?;
+
+    let mut screensaver = Screensaver {
+        conn,
+        window,
+        gc,
+        depth: 0,
+        size: (0, 0),
+        cats: Vec::new(),
+        prints: Vec::new(),
+        spawned: 0,
+    };
+    screensaver.update_size()?;
+    screensaver.spawn_cats(cat_count);
+    control::allow_herd();
+    if let Err(err) = screensaver.clear() {
+        tracing::warn!("Error clearing the screensaver window: {err}");
+    }
+
+    let mut first_motion: Option<(i32, i32)> = None;
+    // Screensavers are stopped all the time, so they go without an exit animation.
+    while !shutdown::requested() {
+        while let Some(event) = screensaver
+            .conn
+            .poll_for_event()
+            .context("polling for X11 events")?
+        {
+            match event {
+                Event::KeyPress(_) | Event::ButtonPress(_) => return Ok(()),
+                Event::MotionNotify(motion) => {
+                    let position = (motion.root_x.into(), motion.root_y.into());
+                    let (x, y) = *first_motion.get_or_insert(position);
+                    if (position.0 - x).abs() > MOTION_THRESHOLD
+                        || (position.1 - y).abs() > MOTION_THRESHOLD
+                    {
+                        return Ok(());
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if screensaver.update_size()? {
+            if let Err(err) = screensaver.clear() {
+                tracing::warn!("Error clearing the screensaver window: {err}");
+            }
+        }
+        screensaver.update();
+
+        let next_update = screensaver
+            .cats
+            .iter()
+            .map(|cat| cat.next_update)
+            .min()
+            .unwrap_or_else(|| Instant::now() + CONTROL_POLL_INTERVAL);
+        thread::sleep(next_update.saturating_duration_since(Instant::now()));
+    }
+    Ok(())
+}
@@ -0,0 +1,211 @@
+//! Validate a sprite sheet and render a contact sheet of its animations.
+//!
+//! Theme authors can run this before shipping new art: every `FrameRef` is checked against the
+//! sheet image, and the contact sheet shows each animation as a labeled row of frames, with
+//! frames that point outside the image drawn as red placeholders.
+
+use std::path::PathBuf;
+
+use image::{imageops::FilterType, DynamicImage, Rgba, RgbaImage};
+
+use crate::font::{self, GLYPH_HEIGHT};
+use crate::oneko;
+use crate::sprite_sheet::{Animation, FrameRef, SheetError, SpriteSheet, ANIMATION_NAMES};
+
+const PADDING: u32 = 4;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+const CHECKER_SIZE: u32 = 4;
+/// The finished contact sheet is blown up this much so the 3x5 font stays readable.
+const CONTACT_SHEET_SCALE: u32 = 2;
+
+const BACKGROUND: Rgba<u8> = Rgba([32, 32, 32, 255]);
+const CHECKER_LIGHT: Rgba<u8> = Rgba([160, 160, 160, 255]);
+const CHECKER_DARK: Rgba<u8> = Rgba([128, 128, 128, 255]);
+const TEXT: Rgba<u8> = Rgba([240, 240, 240, 255]);
+const TEXT_DIM: Rgba<u8> = Rgba([150, 150, 150, 255]);
+const ERROR: Rgba<u8> = Rgba([220, 50, 50, 255]);
+
+pub struct SheetOptions {
+    /// The manifest to check, or `None` for the built-in sheet.
+    pub manifest: Option<PathBuf>,
+    pub contact_sheet: Option<PathBuf>,
+}
+
+fn load(options: &SheetOptions) -> Result<(String, SpriteSheet), SheetError> {
+    let Some(path) = &options.manifest else {
+        return Ok(("built-in".to_string(), oneko::builtin_sprite_sheet()));
+    };
+
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok((name, SpriteSheet::open(path)?))
+}
+
+fn fill(image: &mut RgbaImage, (x, y): (u32, u32), (width, height): (u32, u32), color: Rgba<u8>) {
+    for py in y..(y + height).min(image.height()) {
+        for px in x..(x + width).min(image.width()) {
+            image.put_pixel(px, py, color);
+        }
+    }
+}
+
+fn checkerboard(image: &mut RgbaImage, (x, y): (u32, u32), (width, height): (u32, u32)) {
+    for py in 0..height {
+        for px in 0..width {
+            let light = (px / CHECKER_SIZE + py / CHECKER_SIZE).is_multiple_of(2);
+            let color = if light { CHECKER_LIGHT } else { CHECKER_DARK };
+            image.put_pixel(x + px, y + py, color);
+        }
+    }
+}
+
+/// The timing shown under an animation's name.
+fn timing(animation: &Animation) -> String {
+    match animation.durations.first() {
+        Some(first) if animation.durations.iter().any(|duration| duration != first) => {
+            "varies".to_string()
+        }
+        Some(first) => format!("{}ms", first.as_millis()),
+        None => format!("{}ms", animation.interval.as_millis()),
+    }
+}
+
+fn render_contact_sheet(name: &str, sheet: &SpriteSheet, problem_count: usize) -> RgbaImage {
+    let (nominal_width, nominal_height) = sheet.get_size();
+    // Packed sheets may have frames of different sizes, so leave room for the largest.
+    let (sprite_width, sprite_height) = sheet.frames().into_iter().fold(
+        (nominal_width, nominal_height),
+        |(width, height), frame| {
+            let (_, _, frame_width, frame_height) = frame.bounds(sheet.get_size());
+            (width.max(frame_width), height.max(frame_height))
+        },
+    );
+
+    // Known animations come first in their usual order, anything unexpected after them.
+    let mut rows: Vec<&str> = ANIMATION_NAMES.to_vec();
+    rows.extend(
+        sheet
+            .animations()
+            .map(|(name, _)| name)
+            .filter(|name| !ANIMATION_NAMES.contains(name)),
+    );
+
+    let label_width = rows
+        .iter()
+        .map(|name| font::text_width(name, 1))
+        .max()
+        .unwrap_or(0);
+    let max_frames = sheet
+        .animations()
+        .map(|(_, animation)| animation.frames.len() as u32)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let frames_x = PADDING * 2 + label_width;
+    let row_height = (sprite_height + LINE_HEIGHT).max(LINE_HEIGHT * 2) + PADDING;
+    let title =
+        format!("{name}: {nominal_width}x{nominal_height} sprites, {problem_count} problems");
+    let width = (frames_x + max_frames * (sprite_width + PADDING) + PADDING)
+        .max(font::text_width(&title, 1) + PADDING * 2);
+    let height = PADDING + LINE_HEIGHT + PADDING + rows.len() as u32 * row_height;
+
+    let mut image = RgbaImage::from_pixel(width, height, BACKGROUND);
+    let title_color = if problem_count == 0 { TEXT } else { ERROR };
+    font::draw_text(
+        &mut image,
+        &title,
+        (PADDING as i32, PADDING as i32),
+        1,
+        title_color,
+    );
+
+    for (row, name) in rows.iter().enumerate() {
+        let y = PADDING * 2 + LINE_HEIGHT + row as u32 * row_height;
+        let Some(animation) = sheet.get_animation(name) else {
+            font::draw_text(&mut image, name, (PADDING as i32, y as i32), 1, ERROR);
+            font::draw_text(
+                &mut image,
+                "missing",
+                (PADDING as i32, (y + LINE_HEIGHT) as i32),
+                1,
+                ERROR,
+            );
+            continue;
+        };
+
+        font::draw_text(&mut image, name, (PADDING as i32, y as i32), 1, TEXT);
+        font::draw_text(
+            &mut image,
+            &timing(animation),
+            (PADDING as i32, (y + LINE_HEIGHT) as i32),
+            1,
+            TEXT_DIM,
+        );
+
+        for (index, &frame) in animation.frames.iter().enumerate() {
+            let x = frames_x + index as u32 * (sprite_width + PADDING);
+            let valid = sheet.contains_frame(frame);
+            if valid {
+                checkerboard(&mut image, (x, y), (sprite_width, sprite_height));
+                image::imageops::overlay(
+                    &mut image,
+                    &*sheet.get_frame_view(frame),
+                    x.into(),
+                    y.into(),
+                );
+            } else {
+                fill(&mut image, (x, y), (sprite_width, sprite_height), ERROR);
+            }
+
+            let label = match frame {
+                FrameRef::Cell(column, row) => format!("{column},{row}"),
+                FrameRef::Rect { x, y, .. } => format!("{x},{y}"),
+            };
+            font::draw_text(
+                &mut image,
+                &label,
+                (x as i32, (y + sprite_height + 2) as i32),
+                1,
+                if valid { TEXT_DIM } else { ERROR },
+            );
+        }
+    }
+
+    DynamicImage::from(image)
+        .resize_exact(
+            width * CONTACT_SHEET_SCALE,
+            height * CONTACT_SHEET_SCALE,
+            FilterType::Nearest,
+        )
+        .into_rgba8()
+}
+
+/// Checks the sheet and writes the contact sheet if asked to, returning whether the sheet is
+/// valid.
+pub fn run(options: SheetOptions) -> Result<bool, Box<dyn std::error::Error>> {
+    let (name, sheet) = load(&options)?;
+
+    let problems = sheet.validate();
+    for problem in &problems {
+        println!("{problem}");
+    }
+    let frame_count: usize = sheet
+        .animations()
+        .map(|(_, animation)| animation.frames.len())
+        .sum();
+    println!(
+        "{name}: {} animations, {frame_count} frames, {} problems",
+        sheet.animations().count(),
+        problems.len()
+    );
+
+    if let Some(path) = &options.contact_sheet {
+        render_contact_sheet(&name, &sheet, problems.len()).save(path)?;
+        println!("Wrote contact sheet to {}", path.display());
+    }
+
+    Ok(problems.is_empty())
+}
@@ -0,0 +1,45 @@
+//! Quitting gracefully on SIGTERM and SIGINT.
+//!
+//! The first signal asks every backend to let its cats play the theme's exit animation, tear
+//! down its windows and return, after which the persisted state is flushed. A second signal while
+//! that is going on has the backend return straight away, skipping the rest of the animation but
+//! still cleaning up behind itself, which for the terminal backend means giving the terminal back
+//! in the state it was found.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static FORCED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the program has been asked to quit.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Whether the program has been asked to quit again, and should not wait for the cats.
+pub fn forced() -> bool {
+    FORCED.load(Ordering::Relaxed)
+}
+
+/// Starts listening for SIGTERM and SIGINT on a background thread, calling `wake` on every one
+/// so backends blocked on events notice.
+#[cfg(target_os = "linux")]
+pub fn listen(wake: impl Fn() + Send + 'static) {
+    use signal_hook::{
+        consts::{SIGINT, SIGTERM},
+        iterator::Signals,
+    };
+
+    let mut signals = Signals::new([SIGTERM, SIGINT]).expect("Error registering signal handlers");
+    std::thread::Builder::new()
+        .name("signals".into())
+        .spawn(move || {
+            for _ in signals.forever() {
+                if REQUESTED.swap(true, Ordering::Relaxed) {
+                    FORCED.store(true, Ordering::Relaxed);
+                }
+                wake();
+            }
+        })
+        .expect("Error spawning signal thread");
+}
@@ -0,0 +1,109 @@
+//! Where a new cat shows up.
+//!
+//! ```toml
+//! [spawn]
+//! at = "edge"       # random (default), cursor, edge, top_left, top_right, bottom_left,
+//!                   # bottom_right or a fixed [x, y]
+//! monitor = "DP-1"  # the monitor to show up on instead of the primary one
+//! ```
+//!
+//! Positions are relative to the monitor and kept on it however small it is. A cat coming in from
+//! an edge starts just outside a random one and runs in before it starts chasing anything.
+//!
+//! A cat picking up from the last run shows up where it was instead. Wayland compositors pick
+//! the output themselves, so `monitor` is ignored there.
+
+use image::GenericImageView;
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+
+use crate::oneko::Oneko;
+
+/// How far random spots keep from the monitor's edges, where there is room.
+const MARGIN: i32 = 50;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnConfig {
+    pub at: Placement,
+    /// The name of the monitor to show up on, such as `DP-1`.
+    pub monitor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Placement {
+    #[default]
+    Random,
+    /// Centered on the pointer.
+    Cursor,
+    /// Running in from just outside an edge.
+    Edge,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    /// The top left corner of the cat, relative to the monitor.
+    #[serde(untagged)]
+    Point((i32, i32)),
+}
+
+/// Picks where a new cat shows up on a monitor of `monitor_size`, given the pointer's position
+/// on it if known. A cat coming in from an edge is also told to run in.
+pub fn place(
+    oneko: &mut Oneko,
+    (monitor_width, monitor_height): (i32, i32),
+    cursor: Option<(i32, i32)>,
+) -> (i32, i32) {
+    let (width, height) = oneko.get_frame().dimensions();
+    let (width, height) = (width as i32, height as i32);
+    // How far the cat can go right and down before sticking out of the monitor.
+    let room = (
+        (monitor_width - width).max(0),
+        (monitor_height - height).max(0),
+    );
+    let clamp = |(x, y): (i32, i32)| (x.clamp(0, room.0), y.clamp(0, room.1));
+
+    match oneko.spawn().at {
+        Placement::Random => (random_within(room.0), random_within(room.1)),
+        Placement::Cursor => match cursor {
+            Some((x, y)) => clamp((x - width / 2, y - height / 2)),
+            None => (room.0 / 2, room.1 / 2),
+        },
+        Placement::TopLeft => (0, 0),
+        Placement::TopRight => (room.0, 0),
+        Placement::BottomLeft => (0, room.1),
+        Placement::BottomRight => room,
+        Placement::Point(point) => clamp(point),
+        Placement::Edge => run_in(oneko, (monitor_width, monitor_height)),
+    }
+}
+
+/// Puts the cat just outside a random edge of a monitor of `monitor_size` and tells it to run in,
+/// returning where it starts.
+pub fn run_in(oneko: &mut Oneko, (monitor_width, monitor_height): (i32, i32)) -> (i32, i32) {
+    let (width, height) = oneko.get_frame().dimensions();
+    let (width, height) = (width as i32, height as i32);
+    let room = (
+        (monitor_width - width).max(0),
+        (monitor_height - height).max(0),
+    );
+    // A sliver of the cat stays on the monitor, so backends asking which monitor the window is
+    // on still get an answer.
+    let (heading, position) = *[
+        ((0, 1), (random_within(room.0), 1 - height)),
+        ((-1, 0), (monitor_width - 1, random_within(room.1))),
+        ((0, -1), (random_within(room.0), monitor_height - 1)),
+        ((1, 0), (1 - width, random_within(room.1))),
+    ]
+    .choose(&mut rand::thread_rng())
+    .unwrap();
+    oneko.enter(heading);
+    position
+}
+
+/// A random offset up to `room`, keeping away from both ends where there is room to.
+fn random_within(room: i32) -> i32 {
+    let margin = MARGIN.min(room / 2);
+    rand::thread_rng().gen_range(margin..=room - margin)
+}
@@ -1,40 +1,664 @@
 use core::time;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
 
-use image::{DynamicImage, Frame, GenericImageView, RgbaImage, SubImage};
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, GenericImageView, ImageError, RgbaImage, SubImage,
+};
+use serde::Deserialize;
 
-#[derive(Clone, Copy)]
-pub struct FrameRef(pub u32, pub u32);
+use crate::aseprite;
 
+/// Where a frame is in the sheet image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(untagged)]
+pub enum FrameRef {
+    /// A cell of the `sprite_size` grid, written `[column, row]`.
+    Cell(u32, u32),
+    /// Any rectangle of the image in pixels, for packed sheets that are not laid out on a grid.
+    Rect {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+}
+
+impl FrameRef {
+    /// The frame's `(x, y, width, height)` in pixels, for a sheet cut into `sprite_size` cells.
+    pub fn bounds(self, (cell_width, cell_height): (u32, u32)) -> (u32, u32, u32, u32) {
+        match self {
+            // Saturating, so a cell far off the sheet from a bad manifest fails the bounds checks
+            // rather than wrapping around onto it.
+            FrameRef::Cell(column, row) => (
+                column.saturating_mul(cell_width),
+                row.saturating_mul(cell_height),
+                cell_width,
+                cell_height,
+            ),
+            FrameRef::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => (x, y, width, height),
+        }
+    }
+}
+
+impl fmt::Display for FrameRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameRef::Cell(column, row) => write!(f, "[{column}, {row}]"),
+            FrameRef::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => write!(f, "{width}x{height}+{x}+{y}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Animation {
-    pub frames: &'static [FrameRef],
+    pub frames: Vec<FrameRef>,
     pub interval: time::Duration,
+    /// How long each frame is shown for, overriding `interval`. Empty when every frame gets the
+    /// same time.
+    pub durations: Vec<time::Duration>,
+}
+
+impl Animation {
+    /// How long frame `frame_count` of the loop stays up.
+    pub fn duration(&self, frame_count: usize) -> time::Duration {
+        if self.frames.is_empty() {
+            return self.interval;
+        }
+        self.durations
+            .get(frame_count % self.frames.len())
+            .copied()
+            .unwrap_or(self.interval)
+    }
+}
+
+/// Every animation the cat can play, in the order tools list them.
+pub const ANIMATION_NAMES: &[&str] = &[
+    "idle",
+    "alert",
+    "tired",
+    "sleeping",
+    "scratch_self",
+    "scratch_wall_n",
+    "scratch_wall_e",
+    "scratch_wall_s",
+    "scratch_wall_w",
+    "run_n",
+    "run_ne",
+    "run_e",
+    "run_se",
+    "run_s",
+    "run_sw",
+    "run_w",
+    "run_nw",
+];
+
+/// The on-disk description of a sprite sheet and its animations.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// The sheet image, relative to the manifest.
+    pub image: PathBuf,
+    pub sprite_size: (u32, u32),
+    pub animations: BTreeMap<String, AnimationDef>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationDef {
+    #[serde(default)]
+    pub frames: Vec<FrameRef>,
+    /// Milliseconds between frames. Mirrored animations default to their source's.
+    pub interval: Option<u64>,
+    /// Milliseconds for each frame in turn, for animations that are not evenly timed.
+    #[serde(default)]
+    pub durations: Vec<u64>,
+    /// Another animation to flip horizontally instead of listing frames, such as `run_e` for
+    /// `run_w`. The flipped frames are generated when the sheet loads.
+    pub mirror: Option<String>,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Reads a manifest, resolving its image path against the manifest's directory.
+    pub fn load(path: &Path) -> Result<Self, SheetError> {
+        let text = fs::read_to_string(path).map_err(|err| SheetError::Io(path.into(), err))?;
+        let mut manifest =
+            Self::parse(&text).map_err(|err| SheetError::Manifest(path.into(), err))?;
+        if let Some(dir) = path.parent() {
+            manifest.image = dir.join(&manifest.image);
+        }
+        Ok(manifest)
+    }
+}
+
+fn durations(def: &AnimationDef) -> Vec<time::Duration> {
+    def.durations
+        .iter()
+        .copied()
+        .map(time::Duration::from_millis)
+        .collect()
+}
+
+fn is_aseprite(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+#[derive(Debug)]
+pub enum SheetError {
+    Io(PathBuf, io::Error),
+    Manifest(PathBuf, toml::de::Error),
+    Json(PathBuf, serde_json::Error),
+    Image(PathBuf, ImageError),
+    Invalid(PathBuf, Vec<Problem>),
+    Xbm(PathBuf),
+    NoCharacter(String, Vec<PathBuf>),
+}
+
+impl fmt::Display for SheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SheetError::Io(path, err) => write!(f, "Error reading {}: {err}", path.display()),
+            SheetError::Manifest(path, err) => {
+                write!(f, "Error parsing {}: {err}", path.display())
+            }
+            SheetError::Json(path, err) => write!(f, "Error parsing {}: {err}", path.display()),
+            SheetError::Image(path, err) => write!(f, "Error loading {}: {err}", path.display()),
+            SheetError::Xbm(path) => write!(f, "{} is not a valid XBM file", path.display()),
+            SheetError::NoCharacter(name, dirs) => {
+                write!(f, "Character {name} not found, looked in:")?;
+                for dir in dirs {
+                    write!(f, "\n  {}", dir.display())?;
+                }
+                Ok(())
+            }
+            SheetError::Invalid(path, problems) => {
+                write!(f, "{} has {} problems", path.display(), problems.len())?;
+                for problem in problems {
+                    write!(f, "\n  {problem}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SheetError {}
+
+/// Something wrong with a sprite sheet that would break the cat at runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    EmptySpriteSize,
+    MissingAnimation(String),
+    UnknownAnimation(String),
+    NoFrames(String),
+    ZeroInterval(String),
+    DurationCount {
+        animation: String,
+        frames: usize,
+        durations: usize,
+    },
+    MirrorWithFrames(String),
+    BadMirror {
+        animation: String,
+        source: String,
+    },
+    FrameOutOfBounds {
+        animation: String,
+        index: usize,
+        frame: FrameRef,
+        grid: (u32, u32),
+    },
+    /// An Aseprite tag covering frames the export does not have.
+    TagOutOfRange {
+        tag: String,
+        to: usize,
+        frames: usize,
+    },
+    /// An Aseprite frame packed rotated, which the importer can not undo.
+    RotatedFrame(usize),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::EmptySpriteSize => write!(f, "sprite_size must not be zero"),
+            Problem::MissingAnimation(name) => write!(f, "{name}: animation is missing"),
+            Problem::UnknownAnimation(name) => write!(f, "{name}: unknown animation name"),
+            Problem::NoFrames(name) => write!(f, "{name}: animation has no frames"),
+            Problem::ZeroInterval(name) => write!(f, "{name}: interval must not be zero"),
+            Problem::DurationCount {
+                animation,
+                frames,
+                durations,
+            } => write!(
+                f,
+                "{animation}: {durations} durations given for {frames} frames"
+            ),
+            Problem::MirrorWithFrames(name) => {
+                write!(f, "{name}: give either frames or mirror, not both")
+            }
+            Problem::BadMirror { animation, source } => write!(
+                f,
+                "{animation}: can only mirror an animation with frames of its own, not {source}"
+            ),
+            Problem::FrameOutOfBounds {
+                animation,
+                index,
+                frame: frame @ FrameRef::Cell(..),
+                grid: (columns, rows),
+            } => write!(
+                f,
+                "{animation}: frame {index} {frame} is outside the {columns}x{rows} sheet"
+            ),
+            Problem::FrameOutOfBounds {
+                animation,
+                index,
+                frame,
+                ..
+            } => write!(f, "{animation}: frame {index} {frame} is outside the image"),
+            Problem::TagOutOfRange { tag, to, frames } => write!(
+                f,
+                "{tag}: tag runs to frame {to}, but there are only {frames} frames"
+            ),
+            Problem::RotatedFrame(index) => write!(
+                f,
+                "frame {index} is rotated, export without rotating sprites"
+            ),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct SpriteSheet {
     image: RgbaImage,
     sprite_size: (u32, u32),
+    animations: BTreeMap<String, Animation>,
+    /// Problems found while building the sheet from a manifest, reported by `validate`.
+    manifest_problems: Vec<Problem>,
+    /// The frame each generated mirror image was flipped from.
+    mirrored: HashMap<FrameRef, FrameRef>,
 }
 
 impl SpriteSheet {
-    pub fn new(image: DynamicImage, sprite_size: (u32, u32)) -> Self {
+    pub fn new(
+        image: DynamicImage,
+        sprite_size: (u32, u32),
+        animations: BTreeMap<String, Animation>,
+    ) -> Self {
         Self {
             image: image.into(),
             sprite_size,
+            animations,
+            manifest_problems: Vec::new(),
+            mirrored: HashMap::new(),
         }
     }
 
-    pub fn get_frame_view(&self, frame_ref: FrameRef) -> SubImage<&RgbaImage> {
-        let FrameRef(x, y) = frame_ref;
+    /// Builds a sheet from a manifest and an already decoded image without validating it.
+    pub fn from_manifest(manifest: &Manifest, image: DynamicImage) -> Self {
+        let mut sheet = Self::new(image, manifest.sprite_size, BTreeMap::new());
+        let mut animations: BTreeMap<String, Animation> = manifest
+            .animations
+            .iter()
+            .filter(|(_, def)| def.mirror.is_none())
+            .map(|(name, def)| {
+                let animation = Animation {
+                    frames: def.frames.clone(),
+                    interval: time::Duration::from_millis(def.interval.unwrap_or(0)),
+                    durations: durations(def),
+                };
+                (name.clone(), animation)
+            })
+            .collect();
+
+        let mut mirrors = Vec::new();
+        for (name, def) in &manifest.animations {
+            let Some(source) = &def.mirror else { continue };
+            if !def.frames.is_empty() {
+                sheet
+                    .manifest_problems
+                    .push(Problem::MirrorWithFrames(name.clone()));
+            }
+            match animations.get(source) {
+                Some(source) => mirrors.push((name, def, source.clone())),
+                None => sheet.manifest_problems.push(Problem::BadMirror {
+                    animation: name.clone(),
+                    source: source.clone(),
+                }),
+            }
+        }
+
+        let mut sources: Vec<FrameRef> = Vec::new();
+        for (_, _, source) in &mirrors {
+            for &frame in &source.frames {
+                if sheet.contains_frame(frame) && !sources.contains(&frame) {
+                    sources.push(frame);
+                }
+            }
+        }
+        let flipped = sheet.append_flipped(&sources);
+
+        for (name, def, source) in mirrors {
+            let frames = source
+                .frames
+                .iter()
+                .map(|frame| flipped.get(frame).copied().unwrap_or(*frame))
+                .collect();
+            let interval = def
+                .interval
+                .map_or(source.interval, time::Duration::from_millis);
+            let durations = if def.interval.is_some() || !def.durations.is_empty() {
+                durations(def)
+            } else {
+                source.durations.clone()
+            };
+            animations.insert(
+                name.clone(),
+                Animation {
+                    frames,
+                    interval,
+                    durations,
+                },
+            );
+        }
+
+        sheet.animations = animations;
+        sheet
+    }
+
+    /// Adds horizontally flipped copies of `frames` in new rows below the existing art, and
+    /// returns where each one went.
+    ///
+    /// Sheets on a grid get new grid rows. Frames of any other size go in rows as tall as the
+    /// largest of them, and are referred to by their rectangles.
+    fn append_flipped(&mut self, frames: &[FrameRef]) -> HashMap<FrameRef, FrameRef> {
+        let (width, height) = frames
+            .iter()
+            .map(|frame| frame.bounds(self.sprite_size))
+            .fold(
+                self.sprite_size,
+                |(width, height), (_, _, frame_width, frame_height)| {
+                    (width.max(frame_width), height.max(frame_height))
+                },
+            );
+        if frames.is_empty() || width == 0 || height == 0 {
+            return HashMap::new();
+        }
+
+        let on_grid = (width, height) == self.sprite_size;
+        let columns = (self.image.width() / width).max(1);
+        let rows = if on_grid {
+            self.grid_size().1
+        } else {
+            self.image.height().div_ceil(height)
+        };
+        let extra_rows = (frames.len() as u32).div_ceil(columns);
+        let mut image = RgbaImage::new(self.image.width().max(width), (rows + extra_rows) * height);
+        imageops::replace(&mut image, &self.image, 0, 0);
+
+        let mut flipped = HashMap::new();
+        for (index, &frame) in frames.iter().enumerate() {
+            let index = index as u32;
+            let (column, row) = (index % columns, rows + index / columns);
+            let sprite = imageops::flip_horizontal(&*self.get_frame_view(frame));
+            let (x, y) = (column * width, row * height);
+            imageops::replace(&mut image, &sprite, x.into(), y.into());
+            let target = if on_grid {
+                FrameRef::Cell(column, row)
+            } else {
+                FrameRef::Rect {
+                    x,
+                    y,
+                    width: sprite.width(),
+                    height: sprite.height(),
+                }
+            };
+            flipped.insert(frame, target);
+            self.mirrored.insert(target, frame);
+        }
+
+        self.image = image;
+        flipped
+    }
+
+    /// Reads a manifest and its image without validating them. Files ending in `.json` are
+    /// taken as Aseprite exports.
+    pub fn open(path: &Path) -> Result<Self, SheetError> {
+        if is_aseprite(path) {
+            return aseprite::open(path);
+        }
+        let manifest = Manifest::load(path)?;
+        let image = image::open(&manifest.image)
+            .map_err(|err| SheetError::Image(manifest.image.clone(), err))?;
+        Ok(Self::from_manifest(&manifest, image))
+    }
+
+    /// Reads a manifest and its image, refusing sheets that [`SpriteSheet::validate`] complains
+    /// about.
+    pub fn load(path: &Path) -> Result<Self, SheetError> {
+        let sheet = Self::open(path)?;
+        let problems = sheet.validate();
+        if !problems.is_empty() {
+            return Err(SheetError::Invalid(path.into(), problems));
+        }
+        Ok(sheet)
+    }
+
+    /// The image a manifest or Aseprite export refers to, resolved against its directory.
+    pub fn image_path(path: &Path) -> Option<PathBuf> {
+        if is_aseprite(path) {
+            return aseprite::image_path(path);
+        }
+        Manifest::load(path).ok().map(|manifest| manifest.image)
+    }
+
+    pub fn image_mut(&mut self) -> &mut RgbaImage {
+        &mut self.image
+    }
+
+    /// The number of sprite columns and rows in the sheet.
+    pub fn grid_size(&self) -> (u32, u32) {
         let (width, height) = self.sprite_size;
-        let (x, y) = (x * width, y * height);
+        if width == 0 || height == 0 {
+            return (0, 0);
+        }
+        (self.image.width() / width, self.image.height() / height)
+    }
+
+    /// The frame `frame` is a flipped copy of, if it was generated for a mirrored animation.
+    pub fn mirror_source(&self, frame: FrameRef) -> Option<FrameRef> {
+        self.mirrored.get(&frame).copied()
+    }
+
+    /// Whether the frame is a non-empty part of the image.
+    pub fn contains_frame(&self, frame: FrameRef) -> bool {
+        let (x, y, width, height) = frame.bounds(self.sprite_size);
+        width > 0
+            && height > 0
+            && x.checked_add(width)
+                .is_some_and(|right| right <= self.image.width())
+            && y.checked_add(height)
+                .is_some_and(|bottom| bottom <= self.image.height())
+    }
 
-        return self.image.view(x, y, width, height);
+    /// Every frame some animation shows, once each, leaving out any outside the image.
+    pub fn frames(&self) -> Vec<FrameRef> {
+        let mut frames = Vec::new();
+        for animation in self.animations.values() {
+            for &frame in &animation.frames {
+                if self.contains_frame(frame) && !frames.contains(&frame) {
+                    frames.push(frame);
+                }
+            }
+        }
+        frames
+    }
+
+    /// Adds problems found while importing the sheet, for `validate` to report.
+    pub fn with_problems(mut self, problems: Vec<Problem>) -> Self {
+        self.manifest_problems.extend(problems);
+        self
+    }
+
+    /// Lists everything that would make the sheet unusable, such as frames outside the image.
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = self.manifest_problems.clone();
+        if self.sprite_size.0 == 0 || self.sprite_size.1 == 0 {
+            problems.push(Problem::EmptySpriteSize);
+        }
+
+        for name in ANIMATION_NAMES {
+            if !self.animations.contains_key(*name) {
+                problems.push(Problem::MissingAnimation(name.to_string()));
+            }
+        }
+
+        for (name, animation) in &self.animations {
+            if !ANIMATION_NAMES.contains(&name.as_str()) {
+                problems.push(Problem::UnknownAnimation(name.clone()));
+            }
+            if animation.frames.is_empty() {
+                problems.push(Problem::NoFrames(name.clone()));
+            }
+            if animation.durations.is_empty() && animation.interval.is_zero()
+                || animation.durations.iter().any(time::Duration::is_zero)
+            {
+                problems.push(Problem::ZeroInterval(name.clone()));
+            }
+            if !animation.durations.is_empty()
+                && animation.durations.len() != animation.frames.len()
+            {
+                problems.push(Problem::DurationCount {
+                    animation: name.clone(),
+                    frames: animation.frames.len(),
+                    durations: animation.durations.len(),
+                });
+            }
+            for (index, frame) in animation.frames.iter().enumerate() {
+                if !self.contains_frame(*frame) {
+                    problems.push(Problem::FrameOutOfBounds {
+                        animation: name.clone(),
+                        index,
+                        frame: *frame,
+                        grid: self.grid_size(),
+                    });
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// A copy of the sheet with every sprite blown up `scale` times.
+    ///
+    /// Each sprite is first enlarged by the whole part of `scale` by repeating pixels, then brought
+    /// to its final size with nearest-neighbour sampling. Whole-number scales are therefore exact,
+    /// and fractional ones only ever widen some rows and columns by a single pixel instead of
+    /// blurring the art. The scaled sprites are packed side by side in a single row.
+    pub fn scaled(&self, scale: f64) -> Self {
+        let integer = (scale.floor() as u32).max(1);
+        let scale_size = |(width, height): (u32, u32)| {
+            (
+                ((width as f64 * scale).round() as u32).max(1),
+                ((height as f64 * scale).round() as u32).max(1),
+            )
+        };
+
+        let frames = self.frames();
+        let sprites: Vec<RgbaImage> = frames
+            .iter()
+            .map(|&frame| {
+                let sprite = self.get_frame_view(frame).to_image();
+                let (width, height) = sprite.dimensions();
+                let scaled_size = scale_size((width, height));
+                let mut sprite = imageops::resize(
+                    &sprite,
+                    width * integer,
+                    height * integer,
+                    FilterType::Nearest,
+                );
+                if sprite.dimensions() != scaled_size {
+                    sprite = imageops::resize(
+                        &sprite,
+                        scaled_size.0,
+                        scaled_size.1,
+                        FilterType::Nearest,
+                    );
+                }
+                sprite
+            })
+            .collect();
+
+        let width = sprites.iter().map(RgbaImage::width).sum();
+        let height = sprites.iter().map(RgbaImage::height).max().unwrap_or(0);
+        let mut image = RgbaImage::new(width, height);
+        let mut moved = HashMap::new();
+        let mut x = 0;
+        for (&frame, sprite) in frames.iter().zip(&sprites) {
+            imageops::replace(&mut image, sprite, x.into(), 0);
+            let target = FrameRef::Rect {
+                x,
+                y: 0,
+                width: sprite.width(),
+                height: sprite.height(),
+            };
+            moved.insert(frame, target);
+            x += sprite.width();
+        }
+
+        let animations = self
+            .animations
+            .iter()
+            .map(|(name, animation)| {
+                let mut animation = animation.clone();
+                for frame in &mut animation.frames {
+                    *frame = moved.get(frame).copied().unwrap_or(*frame);
+                }
+                (name.clone(), animation)
+            })
+            .collect();
+
+        Self::new(image.into(), scale_size(self.sprite_size), animations)
+    }
+
+    pub fn get_frame_view(&self, frame_ref: FrameRef) -> SubImage<&RgbaImage> {
+        let (x, y, width, height) = frame_ref.bounds(self.sprite_size);
+        self.image.view(x, y, width, height)
     }
 
     pub fn get_size(&self) -> (u32, u32) {
         self.sprite_size
     }
 
+    pub fn get_animation(&self, name: &str) -> Option<&Animation> {
+        self.animations.get(name)
+    }
+
+    pub fn animations(&self) -> impl Iterator<Item = (&str, &Animation)> {
+        self.animations
+            .iter()
+            .map(|(name, animation)| (name.as_str(), animation))
+    }
+
     pub fn get_anim_view(&self, animation: &Animation, frame_count: usize) -> SubImage<&RgbaImage> {
         self.get_frame_view(animation.frames[frame_count % animation.frames.len()])
     }
@@ -0,0 +1,45 @@
+//! Things for a cat to follow other than the pointer.
+
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+/// A wandering target that jumps to a random point every so often, letting the cat walk there and
+/// nap until it moves again.
+pub struct Roam {
+    target: (i32, i32),
+    next_move: Instant,
+}
+
+impl Roam {
+    pub fn new(bounds: (i32, i32)) -> Self {
+        let mut roam = Self {
+            target: (0, 0),
+            next_move: Instant::now(),
+        };
+        roam.pick(bounds, Instant::now());
+        roam
+    }
+
+    fn pick(&mut self, (width, height): (i32, i32), now: Instant) {
+        let mut rng = rand::thread_rng();
+        self.target = (
+            rng.gen_range(0..width.max(1)),
+            rng.gen_range(0..height.max(1)),
+        );
+        self.next_move = now + Duration::from_secs(rng.gen_range(5..40));
+    }
+
+    /// The current target, moving it first if the cat has had enough time there.
+    pub fn target(&mut self, bounds: (i32, i32)) -> (i32, i32) {
+        self.target_at(bounds, Instant::now())
+    }
+
+    /// Like [`Roam::target`], but on a clock other than the wall clock.
+    pub fn target_at(&mut self, bounds: (i32, i32), now: Instant) -> (i32, i32) {
+        if now >= self.next_move {
+            self.pick(bounds, now);
+        }
+        self.target
+    }
+}
@@ -0,0 +1,246 @@
+//! Run the cat inside a terminal.
+//!
+//! Every character cell shows two vertically stacked sprite pixels using the upper half block
+//! `▀` with truecolor foreground and background colors, so the cat keeps its shape at the usual
+//! 1:2 cell aspect ratio. The pointer comes from the terminal's mouse tracking (SGR 1006 encoded
+//! any-motion events), which works over SSH and inside tmux without any display server.
+//!
+//! `q`, Esc or Ctrl-C sends the cat off with the theme's exit animation, and another press quits
+//! without waiting for it.
+
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::{
+    cursor,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+        MouseEventKind,
+    },
+    execute, queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use image::{GenericImageView, Rgba};
+
+use crate::oneko::Oneko;
+use crate::shutdown;
+use crate::spawn;
+
+/// Puts the terminal into raw mode with mouse tracking and restores it when dropped, including
+/// when unwinding from a panic.
+struct TerminalGuard {
+    stdout: Stdout,
+}
+
+impl TerminalGuard {
+    fn new() -> io::Result<Self> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(
+            stdout,
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            cursor::Hide,
+            terminal::Clear(terminal::ClearType::All)
+        )?;
+        Ok(Self { stdout })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(
+            self.stdout,
+            ResetColor,
+            cursor::Show,
+            DisableMouseCapture,
+            LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Scaled sprite pixels per terminal column. Terminals have no scale factor, so the sheet is
+/// blown up as the theme asks or as it would be on a plain 96 DPI monitor, and sampled back down
+/// so that every column shows one pixel of the theme's own sprites.
+fn scale(oneko: &Oneko) -> f64 {
+    oneko.scale().max(1.0)
+}
+
+/// A rectangle of character cells, stored as `(column, row, columns, rows)`.
+type CellRect = (u16, u16, u16, u16);
+
+/// The cells covered by a frame of `size` sprite pixels drawn at `position`.
+fn cell_rect((x, y): (i32, i32), (width, height): (u32, u32), scale: f64) -> CellRect {
+    let (x, y) = (f64::from(x.max(0)), f64::from(y.max(0)));
+    let left = (x / scale) as i32;
+    let right = ((x + f64::from(width)) / scale).ceil() as i32;
+    let top = (y / scale / 2.0) as i32;
+    let bottom = (((y + f64::from(height)) / scale).ceil() / 2.0).ceil() as i32;
+    (
+        left as u16,
+        top as u16,
+        (right - left) as u16,
+        (bottom - top) as u16,
+    )
+}
+
+fn union((ax, ay, aw, ah): CellRect, (bx, by, bw, bh): CellRect) -> CellRect {
+    let (x, y) = (ax.min(bx), ay.min(by));
+    let right = (ax + aw).max(bx + bw);
+    let bottom = (ay + ah).max(by + bh);
+    (x, y, right - x, bottom - y)
+}
+
+struct TerminalRenderer {
+    guard: TerminalGuard,
+    size: (u16, u16),
+}
+
+impl TerminalRenderer {
+    /// Redraws the cells in `rect`, showing the cat's current frame at `position`.
+    fn draw(&mut self, oneko: &Oneko, position: (i32, i32), rect: CellRect) -> io::Result<()> {
+        let frame = oneko.get_frame();
+        let scale = scale(oneko);
+        let (columns, rows) = self.size;
+        let (rect_x, rect_y, rect_width, rect_height) = rect;
+
+        // Samples one terminal pixel, going through the scaled sheet one `scale` step at a time.
+        let pixel = |column: i32, pixel_row: i32| -> Option<Rgba<u8>> {
+            let x = (f64::from(column) * scale) as i32 - position.0;
+            let y = (f64::from(pixel_row) * scale) as i32 - position.1;
+            if x < 0 || y < 0 || x >= frame.width() as i32 || y >= frame.height() as i32 {
+                return None;
+            }
+            let pixel = frame.get_pixel(x as u32, y as u32);
+            (pixel[3] >= 128).then_some(pixel)
+        };
+        let color = |Rgba([r, g, b, _]): Rgba<u8>| Color::Rgb { r, g, b };
+
+        let stdout = &mut self.guard.stdout;
+        for row in rect_y..(rect_y + rect_height).min(rows) {
+            queue!(stdout, cursor::MoveTo(rect_x, row))?;
+            for column in rect_x..(rect_x + rect_width).min(columns) {
+                let top = pixel(column.into(), i32::from(row) * 2);
+                let bottom = pixel(column.into(), i32::from(row) * 2 + 1);
+                match (top, bottom) {
+                    (None, None) => queue!(stdout, ResetColor, Print(' '))?,
+                    (Some(top), None) => queue!(
+                        stdout,
+                        ResetColor,
+                        SetForegroundColor(color(top)),
+                        Print('▀')
+                    )?,
+                    (None, Some(bottom)) => queue!(
+                        stdout,
+                        ResetColor,
+                        SetForegroundColor(color(bottom)),
+                        Print('▄')
+                    )?,
+                    (Some(top), Some(bottom)) => queue!(
+                        stdout,
+                        SetForegroundColor(color(top)),
+                        SetBackgroundColor(color(bottom)),
+                        Print('▀')
+                    )?,
+                }
+            }
+        }
+        queue!(stdout, ResetColor)?;
+        stdout.flush()
+    }
+}
+
+pub fn run() -> io::Result<()> {
+    let mut renderer = TerminalRenderer {
+        guard: TerminalGuard::new()?,
+        size: terminal::size()?,
+    };
+
+    let mut oneko = Oneko::default();
+
+    // Positions are kept in scaled sheet pixels; one column is `scale` of them wide and one row
+    // `2 * scale` tall.
+    let monitor_size = |(columns, rows): (u16, u16), scale: f64| {
+        (
+            (f64::from(columns) * scale) as i32,
+            (f64::from(rows) * 2.0 * scale) as i32,
+        )
+    };
+    let (width, height) = monitor_size(renderer.size, scale(&oneko));
+    let mut position = spawn::place(&mut oneko, (width, height), None);
+    let mut cursor_position = (width / 2, height / 2);
+    let mut next_update = Instant::now();
+
+    while !oneko.gone() && !shutdown::forced() {
+        if shutdown::requested() && !oneko.leaving() {
+            oneko.leave();
+            next_update = Instant::now();
+        }
+        let timeout = next_update.saturating_duration_since(Instant::now());
+        if event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    let quit = match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => true,
+                        KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+                        _ => false,
+                    };
+                    // The first press lets the cat play its exit animation, a second one quits
+                    // straight away, the same as a second signal.
+                    if quit && oneko.leaving() {
+                        break;
+                    }
+                    if quit {
+                        oneko.leave();
+                        next_update = Instant::now();
+                    }
+                }
+                Event::Mouse(mouse) => {
+                    let scale = scale(&oneko);
+                    cursor_position = (
+                        (f64::from(mouse.column) * scale) as i32,
+                        ((f64::from(mouse.row) * 2.0 + 1.0) * scale) as i32,
+                    );
+                    if let MouseEventKind::Down(_) = mouse.kind {
+                        let frame_size = oneko.get_frame().dimensions();
+                        let (x, y, columns, rows) = cell_rect(position, frame_size, scale);
+                        if (x..x + columns).contains(&mouse.column)
+                            && (y..y + rows).contains(&mouse.row)
+                        {
+                            oneko.click();
+                            next_update = Instant::now();
+                        }
+                    }
+                }
+                Event::Resize(columns, rows) => {
+                    renderer.size = (columns, rows);
+                    queue!(
+                        renderer.guard.stdout,
+                        terminal::Clear(terminal::ClearType::All)
+                    )?;
+                    let frame_size = oneko.get_frame().dimensions();
+                    let rect = cell_rect(position, frame_size, scale(&oneko));
+                    renderer.draw(&oneko, position, rect)?;
+                }
+                _ => (),
+            }
+            continue;
+        }
+
+        let old_rect = cell_rect(position, oneko.get_frame().dimensions(), scale(&oneko));
+        let (update_delay, new_position) = oneko.act(
+            position,
+            cursor_position,
+            monitor_size(renderer.size, scale(&oneko)),
+        );
+        position = new_position;
+        next_update = Instant::now() + update_delay.max(Duration::from_millis(1));
+
+        let new_rect = cell_rect(position, oneko.get_frame().dimensions(), scale(&oneko));
+        renderer.draw(&oneko, position, union(old_rect, new_rect))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+//! Paw prints left behind the running cat, fading away after a while.
+//!
+//! Turned on by a `[trail]` table in the config:
+//!
+//! ```toml
+//! [trail]
+//! spacing = 12   # pixels run between two prints, before scaling
+//! lifetime = 3   # seconds until a print has faded away
+//! ```
+//!
+//! Prints are drawn by the backends that cover the whole screen, which are the Wayland overlay,
+//! the screensaver and recordings. The cat-sized windows of the winit and X11 backends have
+//! nowhere to put them and the terminal is too coarse for them, so there a `[trail]` only gets a
+//! warning.
+
+use std::{
+    collections::VecDeque,
+    f64::consts::FRAC_PI_4,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+
+use crate::oneko::Step;
+
+const PAW_COLOR: [u8; 3] = [70, 55, 55];
+/// How opaque a fresh print is.
+const PAW_ALPHA: f64 = 160.0;
+/// The width and height of a print, before scaling.
+const PAW_SIZE: f64 = 8.0;
+
+/// Whether whatever is running draws paw prints at all.
+static DRAWN: AtomicBool = AtomicBool::new(true);
+
+/// Tells whether the backend about to run draws paw prints, so that asking for them where they
+/// never show up gets a warning instead of doing nothing.
+pub fn set_drawn(drawn: bool) {
+    DRAWN.store(drawn, Ordering::Relaxed);
+}
+
+/// Whether paw prints show up anywhere.
+pub fn drawn() -> bool {
+    DRAWN.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrailConfig {
+    /// Pixels the cat runs between two prints, before scaling.
+    pub spacing: f64,
+    /// Seconds a print takes to fade away.
+    pub lifetime: f64,
+}
+
+impl Default for TrailConfig {
+    fn default() -> Self {
+        Self {
+            spacing: 12.0,
+            lifetime: 3.0,
+        }
+    }
+}
+
+struct PawPrint {
+    /// The middle of the print, on screen.
+    center: (f64, f64),
+    /// Which way the toes point, as a unit vector.
+    heading: (f64, f64),
+    scale: f64,
+    left_at: Instant,
+}
+
+#[derive(Default)]
+pub struct Trail {
+    prints: VecDeque<PawPrint>,
+    /// How far the cat has run since the last print.
+    travelled: f64,
+    last_position: Option<(i32, i32)>,
+    /// Whether the next print is a left paw.
+    left: bool,
+}
+
+impl Trail {
+    /// Follows the cat through its last step, leaving a print whenever it has run far enough and
+    /// forgetting prints that have faded away.
+    pub fn update(&mut self, step: &Step, config: Option<TrailConfig>, now: Instant) {
+        let Some(config) = config else {
+            self.prints.clear();
+            self.last_position = None;
+            return;
+        };
+        let lifetime = Duration::try_from_secs_f64(config.lifetime).unwrap_or_default();
+        while self
+            .prints
+            .front()
+            .is_some_and(|print| now.duration_since(print.left_at) >= lifetime)
+        {
+            self.prints.pop_front();
+        }
+
+        let last_position = self.last_position.replace(step.position);
+        let (Some((dx, dy)), Some((last_x, last_y))) = (step.heading, last_position) else {
+            return;
+        };
+        let (x, y) = step.position;
+        self.travelled += f64::hypot((x - last_x).into(), (y - last_y).into());
+
+        let spacing = config.spacing * step.scale;
+        if self.travelled < spacing {
+            return;
+        }
+        self.travelled %= spacing;
+
+        let length = f64::hypot(dx.into(), dy.into());
+        let heading = (dx as f64 / length, dy as f64 / length);
+        // Paws sit either side of the line the cat runs along.
+        let side = if self.left { -1.0 } else { 1.0 } * 2.5 * step.scale;
+        let (width, height) = step.size;
+        let feet = (
+            x as f64 + width as f64 / 2.0,
+            y as f64 + height as f64 - PAW_SIZE * step.scale / 2.0,
+        );
+        self.prints.push_back(PawPrint {
+            center: (feet.0 - heading.1 * side, feet.1 + heading.0 * side),
+            heading,
+            scale: step.scale,
+            left_at: now,
+        });
+        self.left = !self.left;
+    }
+
+    /// Every print still visible at `now`, oldest first, with where its top left corner goes.
+    pub fn sprites(
+        &self,
+        config: Option<TrailConfig>,
+        now: Instant,
+    ) -> Vec<(RgbaImage, (i32, i32))> {
+        let Some(config) = config else {
+            return Vec::new();
+        };
+        self.prints
+            .iter()
+            .filter_map(|print| {
+                let age = now.duration_since(print.left_at).as_secs_f64();
+                let fade = 1.0 - age / config.lifetime;
+                (fade > 0.0).then(|| print.render(fade))
+            })
+            .collect()
+    }
+}
+
+impl PawPrint {
+    /// Draws the print as a pad with three toes in front of it, `fade` opaque.
+    fn render(&self, fade: f64) -> (RgbaImage, (i32, i32)) {
+        let size = (PAW_SIZE * self.scale).ceil().max(1.0) as u32;
+        let half = size as f64 / 2.0;
+        let (hx, hy) = self.heading;
+        let rotate = |angle: f64| {
+            let (sin, cos) = angle.sin_cos();
+            (hx * cos - hy * sin, hx * sin + hy * cos)
+        };
+
+        let s = self.scale;
+        let mut blobs = vec![((half - hx * 0.8 * s, half - hy * 0.8 * s), 1.6 * s)];
+        for angle in [-FRAC_PI_4, 0.0, FRAC_PI_4] {
+            let (tx, ty) = rotate(angle);
+            blobs.push(((half + tx * 2.4 * s, half + ty * 2.4 * s), 0.8 * s));
+        }
+
+        let [r, g, b] = PAW_COLOR;
+        let alpha = (PAW_ALPHA * fade).round() as u8;
+        let image = RgbaImage::from_fn(size, size, |x, y| {
+            let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+            let inside = blobs
+                .iter()
+                .any(|&((cx, cy), radius)| f64::hypot(px - cx, py - cy) <= radius);
+            if inside {
+                Rgba([r, g, b, alpha])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        let origin = (
+            (self.center.0 - half).round() as i32,
+            (self.center.1 - half).round() as i32,
+        );
+        (image, origin)
+    }
+}
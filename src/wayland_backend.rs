@@ -0,0 +1,714 @@
+//! A Wayland backend built on wlr-layer-shell.
+//!
+//! Wayland clients can neither position their own windows nor read the global pointer position,
+//! so instead of moving a small window around this backend covers an output with a single
+//! transparent overlay surface and draws the cat inside it. The overlay has an empty input region,
+//! which keeps every click going to the windows below it.
+//!
+//! Since the overlay takes no input, the compositor never tells it where the pointer is, and no
+//! Wayland protocol hands out the global pointer position either. The only source is Hyprland's
+//! IPC socket, which is polled on a background thread, and while it does not answer the cat heads
+//! for where the pointer was last seen. This is a limitation of the backend rather than a missing
+//! feature: on every other compositor the pointer cannot be followed at all, so the cat roams the
+//! output instead, as it does in screensaver mode.
+//!
+//! Running against a headless compositor works the same way as against a real output, and the
+//! ignored test at the bottom of this file checks exactly that:
+//!
+//! ```sh
+//! WLR_BACKENDS=headless sway &
+//! cargo test wayland -- --ignored
+//! ```
+//!
+//! When the output under the overlay goes away the compositor closes it, and a new overlay is
+//! made on whichever output the compositor picks next, with the cat kept where it was.
+
+use std::{
+    env,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use image::{GenericImageView, RgbaImage, SubImage};
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState, Region},
+    delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_shm,
+    output::{OutputHandler, OutputState},
+    reexports::{
+        calloop::EventLoop,
+        calloop_wayland_source::WaylandSource,
+        client::{
+            globals::registry_queue_init,
+            protocol::{wl_output, wl_shm, wl_surface},
+            Connection, QueueHandle,
+        },
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    shell::{
+        wlr_layer::{
+            Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+            LayerSurfaceConfigure,
+        },
+        WaylandSurface,
+    },
+    shm::{
+        slot::{Buffer, SlotPool},
+        Shm, ShmHandler,
+    },
+};
+
+use crate::error::{self, Context, Error, Result};
+use crate::oneko::Oneko;
+use crate::shutdown;
+use crate::spawn;
+use crate::target::Roam;
+use crate::trail::Trail;
+
+/// How often to ask Hyprland where the pointer is.
+const HYPRLAND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The latest global pointer position from Hyprland, shared with the thread asking for it.
+type SharedCursor = Arc<Mutex<Option<(i32, i32)>>>;
+
+/// A part of the buffer, in buffer pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Rect {
+    /// The part of a `width` by `height` image at `(x, y)` that falls inside a buffer of `size`.
+    fn clipped((x, y): (i32, i32), (width, height): (u32, u32), size: (u32, u32)) -> Option<Self> {
+        let left = x.max(0);
+        let top = y.max(0);
+        let right = (x + width as i32).min(size.0 as i32);
+        let bottom = (y + height as i32).min(size.1 as i32);
+        (left < right && top < bottom).then_some(Self {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+
+    /// Makes the rect fully transparent in an `Argb8888` canvas `canvas_width` pixels wide.
+    fn clear(&self, canvas: &mut [u8], canvas_width: u32) {
+        for y in self.y..self.y + self.height {
+            let start = (self.x as usize + y as usize * canvas_width as usize) * 4;
+            canvas[start..start + self.width as usize * 4].fill(0);
+        }
+    }
+}
+
+/// A buffer drawn into and handed to the compositor, kept around so the next frame only has to
+/// redraw what moved.
+struct FrameBuffer {
+    buffer: Buffer,
+    /// Where sprites were drawn into this buffer the last time it was used.
+    painted: Vec<Rect>,
+}
+
+struct OnekoLayer {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    compositor: CompositorState,
+    layer_shell: LayerShell,
+    shm: Shm,
+    pool: SlotPool,
+    /// Buffers for the current buffer size. There are usually two, since the compositor holds on
+    /// to the one on screen until the next one replaces it.
+    buffers: Vec<FrameBuffer>,
+    /// Where sprites are on screen right now, or `None` if the surface shows nothing yet.
+    shown: Option<Vec<Rect>>,
+    layer: LayerSurface,
+    output: Option<wl_output::WlOutput>,
+
+    /// The surface size in logical pixels.
+    size: (u32, u32),
+    /// Buffer pixels per logical pixel, as asked for by the output the overlay is on.
+    buffer_scale: i32,
+    /// Whether the current overlay has been configured and can be drawn to.
+    configured: bool,
+    /// Whether the cat has been given its first position.
+    placed: bool,
+    exit: bool,
+
+    oneko: Oneko,
+    cat_position: (i32, i32),
+    /// Where Hyprland last said the pointer was, in global coordinates, kept up to date by a
+    /// background thread.
+    hyprland_cursor: Option<SharedCursor>,
+    /// Where the pointer was last seen.
+    cursor_position: Option<(i32, i32)>,
+    /// What the cat follows where the pointer has never been seen.
+    roam: Option<Roam>,
+    next_update: Instant,
+    trail: Trail,
+}
+
+impl OnekoLayer {
+    /// The pointer position in surface-local coordinates, if Hyprland has told.
+    fn cursor_position(&self) -> Option<(i32, i32)> {
+        let (x, y) = (*self.hyprland_cursor.as_ref()?.lock().unwrap())?;
+        let (output_x, output_y) = self
+            .output
+            .as_ref()
+            .and_then(|output| self.output_state.info(output))
+            .and_then(|info| info.logical_position)
+            .unwrap_or((0, 0));
+        Some((x - output_x, y - output_y))
+    }
+
+    /// The surface size in buffer pixels, which is what the cat moves in.
+    fn buffer_size(&self) -> (u32, u32) {
+        let scale = self.buffer_scale as u32;
+        (self.size.0 * scale, self.size.1 * scale)
+    }
+
+    fn update(&mut self) {
+        let size = (self.size.0 as i32, self.size.1 as i32);
+        if let Some(cursor_position) = self.cursor_position() {
+            self.cursor_position = Some(cursor_position);
+        }
+        let cursor_position = match self.cursor_position {
+            Some(cursor_position) => cursor_position,
+            None => self
+                .roam
+                .get_or_insert_with(|| Roam::new(size))
+                .target(size),
+        };
+
+        let scale = self.buffer_scale;
+        let (buffer_width, buffer_height) = self.buffer_size();
+        let (update_delay, cat_position) = self.oneko.act(
+            self.cat_position,
+            (cursor_position.0 * scale, cursor_position.1 * scale),
+            (buffer_width as i32, buffer_height as i32),
+        );
+        self.cat_position = cat_position;
+        self.next_update = Instant::now() + update_delay;
+        if let Some(step) = self.oneko.last_step() {
+            self.trail.update(&step, self.oneko.trail(), Instant::now());
+        }
+
+        self.draw();
+    }
+
+    /// Draws the cat and its trail into a free buffer, damaging only where sprites were or are.
+    fn draw(&mut self) {
+        let (width, height) = self.buffer_size();
+        let stride = width as i32 * 4;
+
+        let now = Instant::now();
+        let trail = self.trail.sprites(self.oneko.trail(), now);
+        let frame = self.oneko.get_frame();
+        let mut sprites: Vec<(SubImage<&RgbaImage>, (i32, i32))> = trail
+            .iter()
+            .map(|(sprite, position)| {
+                (
+                    sprite.view(0, 0, sprite.width(), sprite.height()),
+                    *position,
+                )
+            })
+            .collect();
+        sprites.push((frame, self.cat_position));
+        let rects: Vec<Rect> = sprites
+            .iter()
+            .filter_map(|(sprite, position)| {
+                Rect::clipped(*position, sprite.dimensions(), (width, height))
+            })
+            .collect();
+
+        // Reuse whichever buffer the compositor is done with, and only make a new one when both
+        // are still busy.
+        let free = self
+            .buffers
+            .iter()
+            .position(|frame_buffer| frame_buffer.buffer.canvas(&mut self.pool).is_some());
+        let index = match free {
+            Some(index) => index,
+            None => {
+                let (buffer, canvas) = match self.pool.create_buffer(
+                    width as i32,
+                    height as i32,
+                    stride,
+                    wl_shm::Format::Argb8888,
+                ) {
+                    Ok(buffer) => buffer,
+                    Err(err) => {
+                        tracing::warn!("Skipping a frame: {err}");
+                        return;
+                    }
+                };
+                canvas.fill(0);
+                self.buffers.push(FrameBuffer {
+                    buffer,
+                    painted: Vec::new(),
+                });
+                self.buffers.len() - 1
+            }
+        };
+
+        let frame_buffer = &mut self.buffers[index];
+        let canvas = frame_buffer
+            .buffer
+            .canvas(&mut self.pool)
+            .expect("the buffer was just checked to be free");
+        for rect in &frame_buffer.painted {
+            rect.clear(canvas, width);
+        }
+        for (sprite, position) in &sprites {
+            blit(canvas, (width, height), sprite, *position);
+        }
+        frame_buffer.painted = rects.clone();
+
+        let surface = self.layer.wl_surface();
+        surface.set_buffer_scale(self.buffer_scale);
+        // Whatever buffer this is, outside the sprites shown before and now it matches the screen.
+        match self.shown.replace(rects.clone()) {
+            Some(shown) => {
+                for rect in shown.iter().chain(&rects) {
+                    surface.damage_buffer(rect.x, rect.y, rect.width, rect.height);
+                }
+            }
+            None => surface.damage_buffer(0, 0, width as i32, height as i32),
+        }
+        if let Err(err) = frame_buffer.buffer.attach_to(surface) {
+            tracing::warn!("Skipping a frame: {err}");
+            return;
+        }
+        self.layer.commit();
+    }
+
+    /// Forgets every buffer, for when the buffer size changes or a new overlay replaces the old.
+    fn drop_buffers(&mut self) {
+        self.buffers.clear();
+        self.shown = None;
+    }
+}
+
+/// Copies a frame into an `Argb8888` canvas, premultiplying alpha as Wayland expects.
+fn blit(
+    canvas: &mut [u8],
+    (canvas_width, canvas_height): (u32, u32),
+    data: &SubImage<&RgbaImage>,
+    (x, y): (i32, i32),
+) {
+    for (frame_x, frame_y, image::Rgba([r, g, b, a])) in data.pixels() {
+        let (canvas_x, canvas_y) = (x + frame_x as i32, y + frame_y as i32);
+        if canvas_x < 0
+            || canvas_y < 0
+            || canvas_x >= canvas_width as i32
+            || canvas_y >= canvas_height as i32
+        {
+            continue;
+        }
+
+        let premultiply = |channel: u8| (channel as u32 * a as u32 / 255) as u8;
+        let index = (canvas_x as usize + canvas_y as usize * canvas_width as usize) * 4;
+        canvas[index..index + 4].copy_from_slice(&[
+            premultiply(b),
+            premultiply(g),
+            premultiply(r),
+            a,
+        ]);
+    }
+}
+
+/// Starts asking Hyprland for the pointer position on a background thread, returning where the
+/// latest answer goes, or `None` if this is not Hyprland.
+///
+/// Hyprland closes its request socket after every reply, so there is no connection to keep open
+/// between polls. Polling off the main thread keeps a slow answer from ever holding up the cat.
+fn watch_hyprland_cursor() -> Option<SharedCursor> {
+    env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+    let cursor = Arc::new(Mutex::new(None));
+    let latest = Arc::clone(&cursor);
+    thread::Builder::new()
+        .name("hyprland".into())
+        .spawn(move || loop {
+            if let Some(position) = hyprland_cursor_position() {
+                *latest.lock().unwrap() = Some(position);
+            }
+            thread::sleep(HYPRLAND_POLL_INTERVAL);
+        })
+        .expect("Error spawning Hyprland thread");
+    Some(cursor)
+}
+
+/// Asks Hyprland for the global pointer position over its IPC socket.
+fn hyprland_cursor_position() -> Option<(i32, i32)> {
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+    let socket_paths = [
+        env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("hypr")),
+        Some(PathBuf::from("/tmp/hypr")),
+    ];
+
+    let mut stream = socket_paths
+        .into_iter()
+        .flatten()
+        .find_map(|dir| UnixStream::connect(dir.join(&signature).join(".socket.sock")).ok())?;
+    stream
+        .set_read_timeout(Some(Duration::from_millis(50)))
+        .ok()?;
+    stream.write_all(b"j/cursorpos").ok()?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).ok()?;
+
+    // The reply is a tiny JSON object of the form `{"x": 12, "y": 34}`.
+    let field = |name: &str| -> Option<i32> {
+        let start = reply.find(&format!("\"{name}\""))? + name.len() + 2;
+        let value = reply[start..].trim_start_matches([':', ' ']);
+        let end = value
+            .find(|c: char| c != '-' && !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        value[..end].parse().ok()
+    };
+
+    Some((field("x")?, field("y")?))
+}
+
+pub fn run() -> Result<()> {
+    let (mut event_loop, mut state) = connect()?;
+    while !state.exit {
+        // Nothing can be drawn before the first configure, so block until it arrives.
+        let timeout = state
+            .configured
+            .then(|| state.next_update.saturating_duration_since(Instant::now()));
+        event_loop
+            .dispatch(timeout, &mut state)
+            .context("dispatching Wayland events")?;
+
+        if shutdown::requested() && !state.oneko.leaving() {
+            state.oneko.leave();
+            state.next_update = Instant::now();
+        }
+        if state.configured && Instant::now() >= state.next_update {
+            state.update();
+        }
+        if state.oneko.gone() || shutdown::forced() {
+            state.exit = true;
+        }
+    }
+    Ok(())
+}
+
+/// Connects to the compositor and asks it for an overlay, which gets drawn to once the returned
+/// event loop has dispatched its first configure.
+fn connect() -> Result<(EventLoop<'static, OnekoLayer>, OnekoLayer)> {
+    let conn = error::connect("Wayland", Connection::connect_to_env)?;
+    let (globals, event_queue) =
+        registry_queue_init(&conn).context("initializing the Wayland registry")?;
+    let qh = event_queue.handle();
+
+    let event_loop: EventLoop<OnekoLayer> =
+        EventLoop::try_new().context("creating the event loop")?;
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(event_loop.handle())
+        .map_err(|err| err.error)
+        .context("inserting the Wayland source")?;
+
+    let unsupported = |missing| {
+        move |_| Error::Unsupported {
+            server: "The Wayland compositor",
+            missing,
+        }
+    };
+    let compositor = CompositorState::bind(&globals, &qh).map_err(unsupported("wl_compositor"))?;
+    let layer_shell = LayerShell::bind(&globals, &qh).map_err(unsupported("wlr-layer-shell"))?;
+    let shm = Shm::bind(&globals, &qh).map_err(unsupported("wl_shm"))?;
+
+    let hyprland_cursor = watch_hyprland_cursor();
+    if hyprland_cursor.is_none() {
+        tracing::warn!(
+            "Only Hyprland tells where the pointer is on Wayland, so the cat roams instead"
+        );
+    }
+
+    let layer = create_layer(&compositor, &layer_shell, &qh)?;
+    let pool = SlotPool::new(256 * 256 * 4, &shm).context("creating the shm pool")?;
+
+    let state = OnekoLayer {
+        registry_state: RegistryState::new(&globals),
+        output_state: OutputState::new(&globals, &qh),
+        compositor,
+        layer_shell,
+        shm,
+        pool,
+        buffers: Vec::new(),
+        shown: None,
+        layer,
+        output: None,
+
+        size: (0, 0),
+        buffer_scale: 1,
+        configured: false,
+        placed: false,
+        exit: false,
+
+        oneko: Oneko::default(),
+        cat_position: (0, 0),
+        hyprland_cursor,
+        cursor_position: None,
+        roam: None,
+        next_update: Instant::now(),
+        trail: Trail::default(),
+    };
+    Ok((event_loop, state))
+}
+
+/// Makes a click-through overlay covering whichever output the compositor puts it on.
+fn create_layer(
+    compositor: &CompositorState,
+    layer_shell: &LayerShell,
+    qh: &QueueHandle<OnekoLayer>,
+) -> Result<LayerSurface> {
+    let surface = compositor.create_surface(qh);
+    let layer = layer_shell.create_layer_surface(qh, surface, Layer::Overlay, Some("oneko"), None);
+    layer.set_anchor(Anchor::TOP | Anchor::BOTTOM | Anchor::LEFT | Anchor::RIGHT);
+    layer.set_exclusive_zone(-1);
+    layer.set_keyboard_interactivity(KeyboardInteractivity::None);
+
+    let input_region = Region::new(compositor).context("creating the input region")?;
+    layer.set_input_region(Some(input_region.wl_region()));
+    layer.commit();
+    Ok(layer)
+}
+
+impl CompositorHandler for OnekoLayer {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        // Keep the cat where it was on screen while its coordinates change units.
+        let old_factor = self.buffer_scale;
+        self.cat_position = (
+            self.cat_position.0 * new_factor / old_factor,
+            self.cat_position.1 * new_factor / old_factor,
+        );
+        self.buffer_scale = new_factor;
+        self.oneko.set_scale_factor(new_factor.into());
+        self.drop_buffers();
+        if self.configured {
+            self.draw();
+        }
+    }
+
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_transform: wl_output::Transform,
+    ) {
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+    }
+
+    fn surface_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
+    ) {
+        self.output = Some(output.clone());
+        let name = self.output_state.info(output).and_then(|info| info.name);
+        self.oneko.set_monitor(name);
+    }
+
+    fn surface_leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl OutputHandler for OnekoLayer {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl LayerShellHandler for OnekoLayer {
+    fn closed(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, _layer: &LayerSurface) {
+        if self.oneko.leaving() || self.output_state.outputs().next().is_none() {
+            self.exit = true;
+            return;
+        }
+        // The output went away, so start over on another one.
+        tracing::info!("overlay closed, making a new one");
+        match create_layer(&self.compositor, &self.layer_shell, qh) {
+            Ok(layer) => self.layer = layer,
+            Err(err) => {
+                tracing::warn!("{err}");
+                self.exit = true;
+                return;
+            }
+        }
+        self.output = None;
+        self.configured = false;
+        self.drop_buffers();
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _layer: &LayerSurface,
+        configure: LayerSurfaceConfigure,
+        _serial: u32,
+    ) {
+        let (width, height) = configure.new_size;
+        tracing::debug!(width, height, "overlay configured");
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        if !self.placed {
+            let (cat_width, cat_height) = self.oneko.get_frame().dimensions();
+            let scale = self.buffer_scale;
+            let buffer_size = (width as i32 * scale, height as i32 * scale);
+            self.cat_position = match self.oneko.restore() {
+                Some(saved) => {
+                    saved.position_within(buffer_size, (cat_width as i32, cat_height as i32))
+                }
+                None => {
+                    let cursor = self.cursor_position().map(|(x, y)| (x * scale, y * scale));
+                    spawn::place(&mut self.oneko, buffer_size, cursor)
+                }
+            };
+            self.placed = true;
+        }
+        self.configured = true;
+
+        if self.size != (width, height) {
+            self.size = (width, height);
+            self.drop_buffers();
+        }
+        self.draw();
+    }
+}
+
+impl ShmHandler for OnekoLayer {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+delegate_compositor!(OnekoLayer);
+delegate_output!(OnekoLayer);
+delegate_shm!(OnekoLayer);
+delegate_layer!(OnekoLayer);
+delegate_registry!(OnekoLayer);
+
+impl ProvidesRegistryState for OnekoLayer {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+    registry_handlers![OutputState];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clips_rects_to_the_buffer() {
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        assert_eq!(
+            Rect::clipped((10, 20), (32, 32), (100, 100)),
+            Some(rect(10, 20, 32, 32))
+        );
+        assert_eq!(
+            Rect::clipped((-8, 90), (32, 32), (100, 100)),
+            Some(rect(0, 90, 24, 10))
+        );
+        assert_eq!(Rect::clipped((100, 0), (32, 32), (100, 100)), None);
+        assert_eq!(Rect::clipped((-32, -32), (32, 32), (100, 100)), None);
+    }
+
+    #[test]
+    #[ignore = "needs a wlr-layer-shell compositor, such as `WLR_BACKENDS=headless sway`"]
+    fn draws_on_a_headless_compositor() {
+        let (mut event_loop, mut state) = connect().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !state.configured {
+            assert!(
+                Instant::now() < deadline,
+                "the overlay was never configured"
+            );
+            event_loop
+                .dispatch(Duration::from_millis(100), &mut state)
+                .unwrap();
+        }
+        assert!(state.size.0 > 0 && state.size.1 > 0);
+
+        // A couple of frames in a row has the compositor hold one buffer while the next is drawn.
+        for _ in 0..4 {
+            state.next_update = Instant::now();
+            state.update();
+            event_loop
+                .dispatch(Duration::from_millis(50), &mut state)
+                .unwrap();
+        }
+        assert!(!state.buffers.is_empty() && state.buffers.len() <= 2);
+        let shown = state.shown.as_ref().unwrap();
+        assert!(!shown.is_empty());
+        assert!(shown.iter().all(|rect| {
+            let (width, height) = state.buffer_size();
+            rect.x + rect.width <= width as i32 && rect.y + rect.height <= height as i32
+        }));
+    }
+}
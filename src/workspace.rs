@@ -0,0 +1,175 @@
+//! Which virtual desktops the cat shows up on, through the EWMH `_NET_CURRENT_DESKTOP` and
+//! `_NET_WM_DESKTOP` properties.
+//!
+//! ```toml
+//! workspaces = "follow"   # wm (default), sticky, follow or per_workspace
+//! ```
+//!
+//! - `wm` leaves it to the window manager, which usually keeps the cat on the desktop it was
+//!   started on.
+//! - `sticky` shows the cat on every desktop.
+//! - `follow` takes the cat along to whichever desktop is switched to, running in from an edge.
+//! - `per_workspace` gives every desktop a cat of its own, which carries on where it left off
+//!   when its desktop is switched back to.
+//!
+//! The x11 backend's window belongs to no desktop and is shown on all of them, so there `wm` is
+//! the same as `sticky`. Wayland has nothing like it for overlays, so the setting does nothing
+//! there.
+
+use serde::Deserialize;
+#[cfg(target_os = "linux")]
+use x11rb::{
+    connection::Connection,
+    errors::{ConnectionError, ReplyError},
+    protocol::{
+        xproto::{
+            self, AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConnectionExt as _,
+            EventMask, PropMode,
+        },
+        Event,
+    },
+    wrapper::ConnectionExt as _,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Workspaces {
+    #[default]
+    Wm,
+    Sticky,
+    Follow,
+    PerWorkspace,
+}
+
+/// The `_NET_WM_DESKTOP` of a window shown on every desktop.
+#[cfg(target_os = "linux")]
+pub const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+
+/// The desktop properties of one screen.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy)]
+pub struct Desktops {
+    root: xproto::Window,
+    current_desktop: xproto::Atom,
+    wm_desktop: xproto::Atom,
+}
+
+#[cfg(target_os = "linux")]
+impl Desktops {
+    pub fn new(conn: &impl Connection, root: xproto::Window) -> Result<Self, ReplyError> {
+        let atom = |name: &[u8]| -> Result<xproto::Atom, ReplyError> {
+            Ok(conn.intern_atom(false, name)?.reply()?.atom)
+        };
+        Ok(Self {
+            root,
+            current_desktop: atom(b"_NET_CURRENT_DESKTOP")?,
+            wm_desktop: atom(b"_NET_WM_DESKTOP")?,
+        })
+    }
+
+    /// Asks for a `PropertyNotify` whenever the root window's properties change, which includes
+    /// switching desktops.
+    pub fn watch(&self, conn: &impl Connection) -> Result<(), ConnectionError> {
+        conn.change_window_attributes(
+            self.root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        Ok(())
+    }
+
+    /// Whether `event` says the desktop was switched.
+    pub fn switched(&self, event: &Event) -> bool {
+        matches!(event, Event::PropertyNotify(notify)
+            if notify.window == self.root && notify.atom == self.current_desktop)
+    }
+
+    /// The desktop being shown, if the window manager says.
+    pub fn current(&self, conn: &impl Connection) -> Option<u32> {
+        conn.get_property(
+            false,
+            self.root,
+            self.current_desktop,
+            AtomEnum::CARDINAL,
+            0,
+            1,
+        )
+        .ok()?
+        .reply()
+        .ok()?
+        .value32()?
+        .next()
+    }
+
+    /// Puts `window` on `desktop`, or on every desktop for [`ALL_DESKTOPS`]. The property covers
+    /// a window the window manager has not picked up yet, and the message one it already has.
+    pub fn move_window(
+        &self,
+        conn: &impl Connection,
+        window: xproto::Window,
+        desktop: u32,
+    ) -> Result<(), ConnectionError> {
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            self.wm_desktop,
+            AtomEnum::CARDINAL,
+            &[desktop],
+        )?;
+        // The 1 says the request comes from an application rather than a pager.
+        let message = ClientMessageEvent::new(32, window, self.wm_desktop, [desktop, 1, 0, 0, 0]);
+        conn.send_event(
+            false,
+            self.root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            message,
+        )?;
+        conn.flush()
+    }
+}
+
+#[cfg(target_os = "linux")]
+thread_local! {
+    /// The desktop properties of the display winit windows are on, once looked up.
+    static DESKTOPS: std::cell::Cell<Option<Desktops>> = const { std::cell::Cell::new(None) };
+}
+
+/// Runs `body` with the shared X connection and its first screen's desktop properties.
+#[cfg(target_os = "linux")]
+fn with_desktops<R>(
+    body: impl FnOnce(&x11rb::xcb_ffi::XCBConnection, Desktops) -> Option<R>,
+) -> Option<R> {
+    crate::native_utils::with_x11_connection(|conn| {
+        let desktops = match DESKTOPS.get() {
+            Some(desktops) => desktops,
+            None => {
+                let root = conn.setup().roots.first()?.root;
+                let desktops = Desktops::new(conn, root).ok()?;
+                DESKTOPS.set(Some(desktops));
+                desktops
+            }
+        };
+        body(conn, desktops)
+    })?
+}
+
+/// The desktop being shown on the display winit windows are on, if it is X11.
+#[cfg(target_os = "linux")]
+pub fn current_desktop() -> Option<u32> {
+    with_desktops(|conn, desktops| desktops.current(conn))
+}
+
+/// Puts a winit window on `desktop`, where it is an X11 window.
+#[cfg(target_os = "linux")]
+pub fn move_window(window: &winit::window::Window, desktop: u32) {
+    use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+    let id = match window.window_handle().map(|handle| handle.as_raw()) {
+        Ok(RawWindowHandle::Xlib(handle)) => handle.window as xproto::Window,
+        Ok(RawWindowHandle::Xcb(handle)) => handle.window.get(),
+        _ => return,
+    };
+    let moved = with_desktops(|conn, desktops| Some(desktops.move_window(conn, id, desktop)));
+    if let Some(Err(err)) = moved {
+        tracing::warn!("Error moving the window to desktop {desktop}: {err}");
+    }
+}
@@ -0,0 +1,510 @@
+//! A pure x11rb backend for the cat window.
+//!
+//! winit needs its own X connection next to the one in `native_utils`, positions windows through
+//! the window manager and has no notion of override-redirect windows. This backend does everything
+//! on a single `XCBConnection`: it creates an override-redirect ARGB window, moves it with
+//! `configure_window`, presents frames with `put_image` and queries the pointer directly.
+//!
+//! Monitors are listed again whenever RandR says the screen changed. A cat whose monitor went
+//! away moves onto the primary one, and a tick where the pointer cannot be found is skipped.
+//!
+//! The override-redirect window is on every desktop. Following the user runs the cat in again
+//! when `_NET_CURRENT_DESKTOP` changes, and with a cat per desktop the one that was out is put
+//! away until its desktop comes back.
+
+use std::collections::HashMap;
+use std::mem;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::{GenericImageView, RgbaImage, SubImage};
+use x11rb::{
+    connection::Connection,
+    errors::ConnectionError,
+    protocol::{
+        randr,
+        shape::{self, SK, SO},
+        xproto::{
+            self, ClipOrdering, ColormapAlloc, ConfigureWindowAux, ConnectionExt as _, CreateGCAux,
+            CreateWindowAux, EventMask, ImageFormat, PropMode, VisualClass, WindowClass,
+        },
+        Event,
+    },
+    wrapper::ConnectionExt as _,
+    xcb_ffi::XCBConnection,
+};
+
+use crate::error::{self, Context, Error, Result};
+use crate::oneko::{self, Oneko};
+use crate::shutdown;
+use crate::spawn::{self, Placement};
+use crate::workspace::{Desktops, Workspaces};
+
+/// How long to wait before trying again after a tick had to be skipped.
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A monitor rectangle in root window coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Monitor {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub primary: bool,
+    /// The RandR name of the monitor, such as `DP-1`, or `NONE` for the whole screen.
+    pub name: xproto::Atom,
+    /// The monitor's DPI relative to 96, in steps of a quarter.
+    pub scale_factor: f64,
+}
+
+impl Monitor {
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// Lists the active monitors through RandR, falling back to the whole screen.
+#[tracing::instrument(level = "debug", skip_all, ret)]
+pub fn get_monitors(conn: &impl Connection, screen: &xproto::Screen) -> Vec<Monitor> {
+    let monitors = randr::get_monitors(conn, screen.root, true)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .map(|reply| {
+            reply
+                .monitors
+                .iter()
+                .map(|monitor| Monitor {
+                    x: monitor.x.into(),
+                    y: monitor.y.into(),
+                    width: monitor.width.into(),
+                    height: monitor.height.into(),
+                    primary: monitor.primary,
+                    name: monitor.name,
+                    scale_factor: scale_factor(monitor.width, monitor.width_in_millimeters),
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if monitors.is_empty() {
+        vec![Monitor {
+            x: 0,
+            y: 0,
+            width: screen.width_in_pixels.into(),
+            height: screen.height_in_pixels.into(),
+            primary: true,
+            name: x11rb::NONE,
+            scale_factor: scale_factor(screen.width_in_pixels, screen.width_in_millimeters.into()),
+        }]
+    } else {
+        monitors
+    }
+}
+
+/// Looks up the name of a monitor.
+fn monitor_name(conn: &impl Connection, monitor: &Monitor) -> Option<String> {
+    if monitor.name == x11rb::NONE {
+        return None;
+    }
+    let reply = conn.get_atom_name(monitor.name).ok()?.reply().ok()?;
+    String::from_utf8(reply.name).ok()
+}
+
+/// Finds the monitor with the given name.
+fn find_monitor<'a>(
+    conn: &impl Connection,
+    monitors: &'a [Monitor],
+    name: &str,
+) -> Option<&'a Monitor> {
+    let atom = conn
+        .intern_atom(true, name.as_bytes())
+        .ok()?
+        .reply()
+        .ok()?
+        .atom;
+    monitors
+        .iter()
+        .find(|monitor| atom != x11rb::NONE && monitor.name == atom)
+}
+
+/// Works out a scale factor from a monitor's physical size the way most X11 toolkits do.
+/// Projectors and some TVs report nonsense sizes, so anything implausible counts as 96 DPI.
+fn scale_factor(width: u16, width_mm: u32) -> f64 {
+    if width_mm == 0 {
+        return 1.0;
+    }
+    let dpi = width as f64 * 25.4 / width_mm as f64;
+    let scale_factor = (dpi / 96.0 * 4.0).round() / 4.0;
+    if (1.0..=4.0).contains(&scale_factor) {
+        scale_factor
+    } else {
+        1.0
+    }
+}
+
+/// Finds a 32-bit TrueColor visual so the window can have an alpha channel.
+fn find_argb_visual(screen: &xproto::Screen) -> Option<xproto::Visualid> {
+    screen
+        .allowed_depths
+        .iter()
+        .filter(|depth| depth.depth == 32)
+        .flat_map(|depth| depth.visuals.iter())
+        .find(|visual| visual.class == VisualClass::TRUE_COLOR)
+        .map(|visual| visual.visual_id)
+}
+
+/// Converts a frame into the BGRA byte order used by 32 bit ZPixmap images.
+pub fn frame_to_bgra(data: &SubImage<&RgbaImage>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity((data.width() * data.height() * 4) as usize);
+    for (_, _, image::Rgba([r, g, b, a])) in data.pixels() {
+        bytes.extend_from_slice(&[b, g, r, a]);
+    }
+    bytes
+}
+
+impl Drop for X11Window {
+    fn drop(&mut self) {
+        let _ = self.conn.free_gc(self.gc);
+        let _ = self.conn.destroy_window(self.window);
+        let _ = self.conn.free_colormap(self.colormap);
+        let _ = self.conn.flush();
+    }
+}
+
+struct X11Window {
+    conn: XCBConnection,
+    screen_num: usize,
+    window: xproto::Window,
+    gc: xproto::Gcontext,
+    colormap: xproto::Colormap,
+    position: (i32, i32),
+    size: (u32, u32),
+}
+
+impl X11Window {
+    #[tracing::instrument(level = "debug", skip(conn), err)]
+    fn new(
+        conn: XCBConnection,
+        screen_num: usize,
+        position: (i32, i32),
+        size: (u32, u32),
+    ) -> Result<Self> {
+        let screen = &conn.setup().roots[screen_num];
+        let root = screen.root;
+        let visual = find_argb_visual(screen).ok_or(Error::Unsupported {
+            server: "X11",
+            missing: "32-bit TrueColor visual",
+        })?;
+
+        let colormap = conn.generate_id().context("allocating a colormap id")?;
+        conn.create_colormap(ColormapAlloc::NONE, colormap, root, visual)
+            .context("creating a colormap")?;
+
+        let window = conn.generate_id().context("allocating a window id")?;
+        conn.create_window(
+            32,
+            window,
+            root,
+            position.0 as i16,
+            position.1 as i16,
+            size.0 as u16,
+            size.1 as u16,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            visual,
+            &CreateWindowAux::new()
+                .background_pixel(0)
+                .border_pixel(0)
+                .colormap(colormap)
+                .override_redirect(1)
+                .event_mask(EventMask::BUTTON_PRESS),
+        )
+        .context("creating the window")?;
+
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            xproto::AtomEnum::WM_NAME,
+            xproto::AtomEnum::STRING,
+            b"oneko",
+        )
+        .context("setting the window name")?;
+
+        // An empty input shape lets clicks through to whatever is below the cat.
+        shape::rectangles(
+            &conn,
+            SO::SET,
+            SK::INPUT,
+            ClipOrdering::UNSORTED,
+            window,
+            0,
+            0,
+            &[],
+        )
+        .context("disabling hit test")?;
+
+        let gc = conn
+            .generate_id()
+            .context("allocating a graphics context id")?;
+        conn.create_gc(gc, window, &CreateGCAux::new())
+            .context("creating a graphics context")?;
+
+        conn.map_window(window).context("mapping the window")?;
+        conn.flush().context("flushing the X11 connection")?;
+
+        Ok(Self {
+            conn,
+            screen_num,
+            window,
+            gc,
+            colormap,
+            position,
+            size,
+        })
+    }
+
+    fn screen(&self) -> &xproto::Screen {
+        &self.conn.setup().roots[self.screen_num]
+    }
+
+    /// The pointer in root window coordinates, if the server could tell.
+    #[tracing::instrument(level = "trace", skip(self), ret)]
+    fn cursor_position(&self) -> Option<(i32, i32)> {
+        let pointer = xproto::query_pointer(&self.conn, self.screen().root)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some((pointer.root_x.into(), pointer.root_y.into()))
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn move_resize(
+        &mut self,
+        position: (i32, i32),
+        size: (u32, u32),
+    ) -> Result<(), ConnectionError> {
+        if position == self.position && size == self.size {
+            return Ok(());
+        }
+
+        self.conn.configure_window(
+            self.window,
+            &ConfigureWindowAux::new()
+                .x(position.0)
+                .y(position.1)
+                .width(size.0)
+                .height(size.1)
+                .stack_mode(xproto::StackMode::ABOVE),
+        )?;
+        self.position = position;
+        self.size = size;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "trace", skip_all, fields(size = ?data.dimensions()), err)]
+    fn present(&self, data: &SubImage<&RgbaImage>) -> Result<(), ConnectionError> {
+        self.conn.put_image(
+            ImageFormat::Z_PIXMAP,
+            self.window,
+            self.gc,
+            data.width() as u16,
+            data.height() as u16,
+            0,
+            0,
+            0,
+            32,
+            &frame_to_bgra(data),
+        )?;
+        self.conn.flush()
+    }
+}
+
+/// A new cat for a desktop that has not had one, on the monitor with the pointer.
+fn desktop_cat(
+    conn: &impl Connection,
+    monitors: &[Monitor],
+    cursor: Option<(i32, i32)>,
+) -> (Oneko, (i32, i32), xproto::Atom) {
+    let mut oneko = Oneko::default();
+    let monitor = cursor
+        .and_then(|cursor| monitors.iter().find(|monitor| monitor.contains(cursor)))
+        .or_else(|| monitors.iter().find(|monitor| monitor.primary))
+        .unwrap_or(&monitors[0]);
+    oneko.set_scale_factor(monitor.scale_factor);
+    oneko.set_monitor(monitor_name(conn, monitor));
+    let cursor = cursor.map(|(x, y)| (x - monitor.x, y - monitor.y));
+    let (x, y) = spawn::place(&mut oneko, (monitor.width, monitor.height), cursor);
+    (oneko, (x + monitor.x, y + monitor.y), monitor.name)
+}
+
+pub fn run() -> Result<()> {
+    let mut oneko = Oneko::default();
+    let saved = oneko.restore();
+
+    let (conn, screen_num) = error::connect("X11", || XCBConnection::connect(None))?;
+    let root = conn.setup().roots[screen_num].root;
+    // Without RandR there are no changes to hear about, and the whole screen is the one monitor.
+    let _ = randr::select_input(
+        &conn,
+        root,
+        randr::NotifyMask::SCREEN_CHANGE | randr::NotifyMask::CRTC_CHANGE,
+    );
+    let mut monitors = get_monitors(&conn, &conn.setup().roots[screen_num]);
+    let cursor = xproto::query_pointer(&conn, root)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .map(|pointer| (i32::from(pointer.root_x), i32::from(pointer.root_y)));
+    let monitor = saved
+        .as_ref()
+        .and_then(|saved| saved.monitor.as_deref())
+        .and_then(|name| find_monitor(&conn, &monitors, name))
+        .or_else(|| {
+            let name = oneko.spawn().monitor.as_deref()?;
+            find_monitor(&conn, &monitors, name)
+        })
+        .or_else(|| {
+            let cursor = cursor.filter(|_| oneko.spawn().at == Placement::Cursor)?;
+            monitors.iter().find(|monitor| monitor.contains(cursor))
+        })
+        .or_else(|| monitors.iter().find(|monitor| monitor.primary))
+        .unwrap_or(&monitors[0]);
+    oneko.set_scale_factor(monitor.scale_factor);
+    oneko.set_monitor(monitor_name(&conn, monitor));
+    let mut monitor_atom = monitor.name;
+    let (width, height) = oneko.get_frame().dimensions();
+
+    let mut position = match &saved {
+        Some(saved) => {
+            let (x, y) = saved.position_within(
+                (monitor.width, monitor.height),
+                (width as i32, height as i32),
+            );
+            (x + monitor.x, y + monitor.y)
+        }
+        None => {
+            let cursor = cursor.map(|(x, y)| (x - monitor.x, y - monitor.y));
+            let (x, y) = spawn::place(&mut oneko, (monitor.width, monitor.height), cursor);
+            (x + monitor.x, y + monitor.y)
+        }
+    };
+    let mut window = X11Window::new(conn, screen_num, position, (width, height))?;
+
+    let workspaces = oneko::workspaces();
+    let desktops = Desktops::new(&window.conn, root)
+        .ok()
+        .filter(|_| matches!(workspaces, Workspaces::Follow | Workspaces::PerWorkspace));
+    if let Some(desktops) = desktops {
+        desktops
+            .watch(&window.conn)
+            .context("watching for desktop switches")?;
+    }
+    let mut desktop = desktops.and_then(|desktops| desktops.current(&window.conn));
+    // The cats of the other desktops, with where they were and on which monitor.
+    let mut put_away: HashMap<u32, (Oneko, (i32, i32), xproto::Atom)> = HashMap::new();
+
+    while !oneko.gone() && !shutdown::forced() {
+        let mut screen_changed = false;
+        let mut switched = false;
+        while let Some(event) = window
+            .conn
+            .poll_for_event()
+            .context("polling for X11 events")?
+        {
+            match event {
+                Event::ButtonPress(_) => oneko.click(),
+                Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_) => {
+                    tracing::info!(?event, "screen changed");
+                    screen_changed = true;
+                }
+                _ if desktops.is_some_and(|desktops| desktops.switched(&event)) => switched = true,
+                _ => (),
+            }
+        }
+        if screen_changed {
+            monitors = get_monitors(&window.conn, window.screen());
+        }
+        let current = desktops
+            .filter(|_| switched && !oneko.leaving())
+            .and_then(|desktops| desktops.current(&window.conn))
+            .filter(|&current| Some(current) != desktop);
+        if let Some(current) = current {
+            tracing::info!(desktop = current, "desktop switched");
+            let monitor = monitors
+                .iter()
+                .find(|monitor| monitor.name == monitor_atom)
+                .or_else(|| monitors.iter().find(|monitor| monitor.primary))
+                .unwrap_or(&monitors[0]);
+            // Without a desktop to leave behind, the cat simply stays where it is.
+            match (workspaces, desktop) {
+                (Workspaces::Follow, Some(_)) => {
+                    let (x, y) = spawn::run_in(&mut oneko, (monitor.width, monitor.height));
+                    position = (x + monitor.x, y + monitor.y);
+                }
+                (Workspaces::PerWorkspace, Some(previous)) => {
+                    let (next, next_position, next_monitor) =
+                        put_away.remove(&current).unwrap_or_else(|| {
+                            let cursor = window.cursor_position();
+                            desktop_cat(&window.conn, &monitors, cursor)
+                        });
+                    let previous_cat = mem::replace(&mut oneko, next);
+                    put_away.insert(previous, (previous_cat, position, monitor_atom));
+                    position = next_position;
+                    monitor_atom = next_monitor;
+                }
+                _ => (),
+            }
+            desktop = Some(current);
+        }
+        if shutdown::requested() {
+            oneko.leave();
+        }
+
+        let Some(cursor_pos) = window.cursor_position() else {
+            thread::sleep(RETRY_INTERVAL);
+            continue;
+        };
+        let (width, height) = oneko.get_frame().dimensions();
+        let (cat_cx, cat_cy) = (
+            position.0 + width as i32 / 2,
+            position.1 + height as i32 / 2,
+        );
+        // A cat running in or off the edge stays with the monitor it is entering or leaving.
+        let monitor = monitors
+            .iter()
+            .find(|monitor| (oneko.entering() || oneko.leaving()) && monitor.name == monitor_atom)
+            .or_else(|| {
+                monitors
+                    .iter()
+                    .find(|monitor| monitor.contains((cat_cx, cat_cy)))
+            })
+            // Halfway between two monitors, or on one that was just unplugged.
+            .or_else(|| monitors.iter().find(|monitor| monitor.name == monitor_atom))
+            .or_else(|| monitors.iter().find(|monitor| monitor.primary))
+            .unwrap_or(&monitors[0]);
+        oneko.set_scale_factor(monitor.scale_factor);
+        if monitor.name != monitor_atom {
+            monitor_atom = monitor.name;
+            oneko.set_monitor(monitor_name(&window.conn, monitor));
+        }
+
+        let (update_delay, new_position) = oneko.act(
+            (position.0 - monitor.x, position.1 - monitor.y),
+            (cursor_pos.0 - monitor.x, cursor_pos.1 - monitor.y),
+            (monitor.width, monitor.height),
+        );
+        let next_update = Instant::now() + update_delay;
+
+        position = (new_position.0 + monitor.x, new_position.1 + monitor.y);
+        let data = oneko.get_frame();
+        let drawn = window
+            .move_resize(position, data.dimensions())
+            .and_then(|()| window.present(&data));
+        if let Err(err) = drawn {
+            tracing::warn!("Skipping a frame: {err}");
+        }
+
+        thread::sleep(next_update.saturating_duration_since(Instant::now()));
+    }
+    Ok(())
+}
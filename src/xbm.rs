@@ -0,0 +1,285 @@
+//! Characters from the original X11 oneko, read from its XBM bitmap and mask files.
+//!
+//! The original program ships each character as a directory of 32x32 bitmaps, with a matching
+//! directory of masks: `bitmaps/dog/*.xbm` and `bitmasks/dog/*_mask.xbm`. Copy both into one
+//! directory named after the character,
+//!
+//! ```text
+//! ~/.local/share/oneko-rust/characters/dog/awake.xbm
+//! ~/.local/share/oneko-rust/characters/dog/awake_mask.xbm
+//! ...
+//! ```
+//!
+//! and `--character dog` picks it up. `tora` uses `neko`'s masks, so copy those in for it. Set
+//! bits are drawn black and clear ones white, which `-fg` and `-bg` then recolor like any other
+//! sheet.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use image::{Rgba, RgbaImage};
+
+use crate::sprite_sheet::{Animation, FrameRef, SheetError, SpriteSheet};
+
+const COLUMNS: u32 = 8;
+const INTERVAL: Duration = Duration::from_millis(125);
+const SLEEP_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The original bitmap names behind each animation.
+const ANIMATIONS: &[(&str, &[&str])] = &[
+    ("idle", &["mati2"]),
+    ("alert", &["awake"]),
+    ("tired", &["mati3"]),
+    ("sleeping", &["sleep1", "sleep2"]),
+    ("scratch_self", &["kaki1", "kaki2"]),
+    ("scratch_wall_n", &["utogi1", "utogi2"]),
+    ("scratch_wall_e", &["rtogi1", "rtogi2"]),
+    ("scratch_wall_s", &["dtogi1", "dtogi2"]),
+    ("scratch_wall_w", &["ltogi1", "ltogi2"]),
+    ("run_n", &["up1", "up2"]),
+    ("run_ne", &["upright1", "upright2"]),
+    ("run_e", &["right1", "right2"]),
+    ("run_se", &["dwright1", "dwright2"]),
+    ("run_s", &["down1", "down2"]),
+    ("run_sw", &["dwleft1", "dwleft2"]),
+    ("run_w", &["left1", "left2"]),
+    ("run_nw", &["upleft1", "upleft2"]),
+];
+
+/// A 1-bit image, one `bool` per pixel.
+struct Bitmap {
+    width: u32,
+    height: u32,
+    bits: Vec<bool>,
+}
+
+impl Bitmap {
+    fn get(&self, x: u32, y: u32) -> bool {
+        self.bits[(y * self.width + x) as usize]
+    }
+}
+
+/// Parses the C source of an XBM file.
+fn parse(text: &str) -> Option<Bitmap> {
+    let define = |suffix: &str| -> Option<u32> {
+        text.lines().find_map(|line| {
+            let mut words = line.split_whitespace();
+            (words.next()? == "#define" && words.next()?.ends_with(suffix))
+                .then(|| words.next()?.parse().ok())
+                .flatten()
+        })
+    };
+    let width = define("_width")?;
+    let height = define("_height")?;
+
+    let (open, close) = (text.find('{')?, text.rfind('}')?);
+    if open >= close {
+        return None;
+    }
+    let data = &text[open + 1..close];
+    let bytes = data
+        .split(',')
+        .map(str::trim)
+        .filter(|byte| !byte.is_empty())
+        .map(
+            |byte| match byte.strip_prefix("0x").or(byte.strip_prefix("0X")) {
+                Some(hex) => u8::from_str_radix(hex, 16).ok(),
+                None => byte.parse().ok(),
+            },
+        )
+        .collect::<Option<Vec<u8>>>()?;
+
+    // Rows are padded to whole bytes, with the leftmost pixel in the lowest bit.
+    let stride = width.div_ceil(8);
+    if bytes.len() < stride.checked_mul(height)? as usize {
+        return None;
+    }
+    let bits = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| bytes[(y * stride + x / 8) as usize] & (1 << (x % 8)) != 0)
+        .collect();
+    Some(Bitmap {
+        width,
+        height,
+        bits,
+    })
+}
+
+fn read(path: &Path) -> Result<Bitmap, SheetError> {
+    let text = fs::read_to_string(path).map_err(|err| SheetError::Io(path.into(), err))?;
+    parse(&text).ok_or_else(|| SheetError::Xbm(path.into()))
+}
+
+/// Where `--character name` is looked for. Anything with a slash is taken as a directory.
+fn character_dirs(name: &str) -> Vec<PathBuf> {
+    if name.contains('/') {
+        return vec![PathBuf::from(name)];
+    }
+
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+    data_home
+        .into_iter()
+        .chain(["/usr/local/share", "/usr/share"].map(PathBuf::from))
+        .map(|dir| dir.join("oneko-rust").join("characters").join(name))
+        .collect()
+}
+
+/// The first of [`character_dirs`] that exists.
+fn character_dir(name: &str) -> Result<PathBuf, SheetError> {
+    let dirs = character_dirs(name);
+    dirs.iter()
+        .find(|dir| dir.is_dir())
+        .cloned()
+        .ok_or_else(|| SheetError::NoCharacter(name.to_string(), dirs))
+}
+
+/// Every frame a character is made of, each drawn from `<frame>.xbm` and `<frame>_mask.xbm`.
+fn frame_names() -> Vec<&'static str> {
+    ANIMATIONS
+        .iter()
+        .flat_map(|(_, frames)| frames.iter().copied())
+        .collect()
+}
+
+/// The bitmap and mask files `--character name` is read from, so a watcher knows what to look
+/// out for. Without a character directory yet, these are where the first place looked would have
+/// them.
+pub fn character_files(name: &str) -> Vec<PathBuf> {
+    let Some(dir) = character_dir(name)
+        .ok()
+        .or_else(|| character_dirs(name).into_iter().next())
+    else {
+        return Vec::new();
+    };
+    frame_names()
+        .into_iter()
+        .flat_map(|frame| [format!("{frame}.xbm"), format!("{frame}_mask.xbm")])
+        .map(|file| dir.join(file))
+        .collect()
+}
+
+/// Builds a sheet from the bitmaps of a classic oneko character.
+pub fn load_character(name: &str) -> Result<SpriteSheet, SheetError> {
+    let dir = character_dir(name)?;
+    let names = frame_names();
+
+    let mut sprites = Vec::new();
+    for name in &names {
+        let bitmap = read(&dir.join(format!("{name}.xbm")))?;
+        let mask = read(&dir.join(format!("{name}_mask.xbm")))?;
+        sprites.push((bitmap, mask));
+    }
+
+    let (width, height) = (sprites[0].0.width, sprites[0].0.height);
+    let rows = (sprites.len() as u32).div_ceil(COLUMNS);
+    let mut image = RgbaImage::new(width * COLUMNS, height * rows);
+    let mut frames = BTreeMap::new();
+    for (index, (name, (bitmap, mask))) in names.iter().zip(&sprites).enumerate() {
+        let (column, row) = (index as u32 % COLUMNS, index as u32 / COLUMNS);
+        for y in 0..height.min(bitmap.height).min(mask.height) {
+            for x in 0..width.min(bitmap.width).min(mask.width) {
+                if !mask.get(x, y) {
+                    continue;
+                }
+                let color = if bitmap.get(x, y) { 0 } else { 255 };
+                image.put_pixel(
+                    column * width + x,
+                    row * height + y,
+                    Rgba([color, color, color, 255]),
+                );
+            }
+        }
+        frames.insert(*name, FrameRef::Cell(column, row));
+    }
+
+    let animations = ANIMATIONS
+        .iter()
+        .map(|(animation, names)| {
+            let interval = if *animation == "sleeping" {
+                SLEEP_INTERVAL
+            } else {
+                INTERVAL
+            };
+            let animation_frames = names.iter().map(|name| frames[name]).collect();
+            (
+                animation.to_string(),
+                Animation {
+                    frames: animation_frames,
+                    interval,
+                    durations: Vec::new(),
+                },
+            )
+        })
+        .collect();
+
+    Ok(SpriteSheet::new(image.into(), (width, height), animations))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BITMAP: &str = "#define cat_width 10
+#define cat_height 2
+static char cat_bits[] = {
+   0x01, 0x02, 0xff, 0X03 };
+";
+
+    #[test]
+    fn parses_bitmaps() {
+        let bitmap = parse(BITMAP).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (10, 2));
+        // Rows are padded to two bytes, with the leftmost pixel in the lowest bit.
+        assert!(bitmap.get(0, 0));
+        assert!(!bitmap.get(1, 0));
+        assert!(bitmap.get(9, 0));
+        assert!(!bitmap.get(8, 0));
+        assert!((0..10).all(|x| bitmap.get(x, 1)));
+    }
+
+    #[test]
+    fn parses_decimal_bytes() {
+        let bitmap = parse("#define a_width 8\n#define a_height 1\n{ 129, }").unwrap();
+        assert!(bitmap.get(0, 0));
+        assert!(bitmap.get(7, 0));
+        assert!(!bitmap.get(3, 0));
+    }
+
+    #[test]
+    fn rejects_malformed_bitmaps() {
+        for text in [
+            "",
+            // No size.
+            "static char cat_bits[] = { 0x01 };",
+            "#define cat_width 8\nstatic char cat_bits[] = { 0x01 };",
+            "#define cat_width eight\n#define cat_height 1\n{ 0x01 }",
+            // No data, or data in the wrong place.
+            "#define cat_width 8\n#define cat_height 1\n",
+            "#define cat_width 8\n#define cat_height 1\n{ 0x01",
+            "#define cat_width 8\n#define cat_height 1\n0x01 }",
+            "#define cat_width 8\n#define cat_height 1\n} 0x01 {",
+            // Bad bytes.
+            "#define cat_width 8\n#define cat_height 1\n{ 0x100 }",
+            "#define cat_width 8\n#define cat_height 1\n{ 0xzz }",
+            // Too few bytes for the size.
+            "#define cat_width 16\n#define cat_height 2\n{ 0x01, 0x02, 0x03 }",
+            "#define cat_width 4294967295\n#define cat_height 4294967295\n{ 0x01 }",
+        ] {
+            assert!(parse(text).is_none(), "{text:?} parsed");
+        }
+    }
+
+    #[test]
+    fn lists_character_files() {
+        let files = character_files("./cats/dog");
+        assert_eq!(files.len(), frame_names().len() * 2);
+        assert!(files.contains(&PathBuf::from("./cats/dog/awake.xbm")));
+        assert!(files.contains(&PathBuf::from("./cats/dog/awake_mask.xbm")));
+    }
+}